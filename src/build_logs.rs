@@ -0,0 +1,293 @@
+use crate::{Deployment, DeploymentLog, Result, Service};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    task::{JoinHandle, JoinSet},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, warn};
+
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Once stopped, a subscription silent for this long has delivered everything
+const DRAIN_IDLE: Duration = Duration::from_secs(3);
+
+/// Position of the last log written, timestamps are shared by bursts of lines so the count
+/// of lines already written at that timestamp is kept too
+#[derive(Debug, Default)]
+struct Cursor {
+    timestamp: Option<DateTime<Utc>>,
+    written: usize,
+}
+
+impl Cursor {
+    fn advance(&mut self, log: &DeploymentLog) {
+        if self.timestamp == Some(log.timestamp()) {
+            self.written += 1;
+        } else {
+            self.timestamp = Some(log.timestamp());
+            self.written = 1;
+        }
+    }
+
+    /// Drops what was already written from a batch queried starting at the cursor
+    fn unseen(&self, logs: Vec<DeploymentLog>) -> Vec<DeploymentLog> {
+        let Some(timestamp) = self.timestamp else {
+            return logs;
+        };
+
+        let mut skipped = 0;
+        logs.into_iter()
+            .filter(|log| match log.timestamp().cmp(&timestamp) {
+                std::cmp::Ordering::Less => false,
+                std::cmp::Ordering::Equal if skipped < self.written => {
+                    skipped += 1;
+                    false
+                }
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+/// Append-only NDJSON file holding one deployment's build logs
+struct LogFile {
+    file: File,
+    cursor: Cursor,
+}
+
+impl LogFile {
+    async fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(Self {
+            file,
+            cursor: Cursor::default(),
+        })
+    }
+
+    /// Flushes every batch so a crash loses at most the batch in flight
+    async fn append(&mut self, logs: &[DeploymentLog]) -> Result<()> {
+        if logs.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = String::new();
+        for log in logs {
+            lines.push_str(&serde_json::to_string(log)?);
+            lines.push('\n');
+            self.cursor.advance(log);
+        }
+        self.file.write_all(lines.as_bytes()).await?;
+        self.file.flush().await?;
+        Ok(())
+    }
+}
+
+/// Streams the build logs of every deployment in a project to disk while it builds,
+/// so builds that time out or crash still leave their logs behind. Dropping it stops following
+pub struct BuildLogFollower {
+    cancel: CancellationToken,
+    discovery: Option<JoinHandle<HashMap<String, PathBuf>>>,
+}
+
+impl BuildLogFollower {
+    /// Logs go to the [`log_path`] of each service instance inside `dir`
+    pub fn start(token: &str, project_id: &str, dir: &Path, code: &str) -> Self {
+        let cancel = CancellationToken::new();
+        let discovery = tokio::spawn(discover(
+            token.to_owned(),
+            project_id.to_owned(),
+            dir.to_owned(),
+            code.to_owned(),
+            cancel.clone(),
+        ));
+
+        Self {
+            cancel,
+            discovery: Some(discovery),
+        }
+    }
+
+    /// Stops following once what's left is written, returning each deployment's log file
+    pub async fn stop(mut self) -> HashMap<String, PathBuf> {
+        self.cancel.cancel();
+
+        let Some(discovery) = self.discovery.take() else {
+            return HashMap::new();
+        };
+        discovery.await.unwrap_or_else(|err| {
+            error!("Build log follower panicked: {err}");
+            HashMap::new()
+        })
+    }
+}
+
+impl Drop for BuildLogFollower {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// `{code}-{service}-{environment_id}.ndjson` inside `dir`, services can be deployed to several
+/// environments
+pub fn log_path(dir: &Path, code: &str, service: &str, environment_id: &str) -> PathBuf {
+    dir.join(format!("{code}-{service}-{environment_id}.ndjson"))
+}
+
+/// Reads back a file written by [`BuildLogFollower`], skipping lines torn by a crash
+pub async fn read(path: &Path) -> Result<Vec<DeploymentLog>> {
+    let ndjson = tokio::fs::read_to_string(path).await?;
+    Ok(ndjson
+        .lines()
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(log) => Some(log),
+            Err(err) => {
+                warn!("Skipping invalid log line in {}: {err}", path.display());
+                None
+            }
+        })
+        .collect())
+}
+
+/// Writes logs fetched after the fact in the same format the follower uses
+pub async fn write(path: &Path, logs: &[DeploymentLog]) -> Result<()> {
+    LogFile::open(path).await?.append(logs).await
+}
+
+/// Deployments only show up once the workflow creates them, keep listing until all have one
+async fn discover(
+    token: String,
+    project_id: String,
+    dir: PathBuf,
+    code: String,
+    cancel: CancellationToken,
+) -> HashMap<String, PathBuf> {
+    let mut files = HashMap::new();
+    let mut followers = JoinSet::new();
+    let mut interval = tokio::time::interval(DISCOVERY_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        let services = match Service::list(&token, &project_id).await {
+            Ok(services) => services,
+            Err(err) => {
+                warn!("Unable to list services to follow build logs of {code}: {err}");
+                continue;
+            }
+        };
+
+        let mut pending = false;
+        for service in &services {
+            for instance in service.instances() {
+                let Some(deployment_id) = instance.deployment_id() else {
+                    pending = true;
+                    continue;
+                };
+                if files.contains_key(deployment_id) {
+                    continue;
+                }
+
+                let path = log_path(&dir, &code, service.name(), instance.environment_id());
+                files.insert(deployment_id.clone(), path.clone());
+                followers.spawn(follow(
+                    token.clone(),
+                    deployment_id.clone(),
+                    path,
+                    cancel.clone(),
+                ));
+            }
+        }
+
+        if !pending && !services.is_empty() {
+            break;
+        }
+    }
+
+    while let Some(result) = followers.join_next().await {
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => error!("Unable to stream build logs of {code}: {err}"),
+            Err(err) => error!("Build log stream of {code} panicked: {err}"),
+        }
+    }
+    files
+}
+
+/// Follows a deployment over a subscription, polling with a cursor when that isn't possible
+async fn follow(
+    token: String,
+    deployment_id: String,
+    path: PathBuf,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let mut file = LogFile::open(&path).await?;
+
+    match Deployment::subscribe_build_logs(&token, &deployment_id).await {
+        Ok(mut subscription) => {
+            loop {
+                let event = tokio::select! {
+                    _ = cancel.cancelled() => {
+                        while let Ok(Some(Ok(event))) =
+                            tokio::time::timeout(DRAIN_IDLE, subscription.next()).await
+                        {
+                            file.append(&event.logs()).await?;
+                        }
+                        let _ = subscription.close().await;
+                        return Ok(());
+                    }
+                    event = subscription.next() => event,
+                };
+
+                match event {
+                    Some(Ok(event)) => file.append(&event.logs()).await?,
+                    Some(Err(err)) => {
+                        warn!("Build log subscription of {deployment_id} failed, polling instead: {err}");
+                        break;
+                    }
+                    None => {
+                        warn!("Build log subscription of {deployment_id} ended, polling instead");
+                        break;
+                    }
+                }
+            }
+        }
+        Err(err) => {
+            warn!("Unable to subscribe to build logs of {deployment_id}, polling instead: {err}")
+        }
+    }
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        let stopped = tokio::select! {
+            _ = cancel.cancelled() => true,
+            _ = interval.tick() => false,
+        };
+
+        match Deployment::build_logs_since(&token, &deployment_id, file.cursor.timestamp).await {
+            Ok(logs) => {
+                let logs = file.cursor.unseen(logs);
+                file.append(&logs).await?;
+            }
+            Err(err) if !stopped => warn!("Unable to poll build logs of {deployment_id}: {err}"),
+            Err(err) => return Err(err),
+        }
+
+        if stopped {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{Error, Result};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[derive(Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CiReportFormat {
+    #[default]
+    Junit,
+    Tap,
+    None,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    #[default]
+    Json,
+    Slack,
+    Discord,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    RunFinished,
+    Regression,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Signs the body with HMAC-SHA256, sent in the `X-Crater-Signature` header
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    #[serde(default = "WebhookConfig::default_events")]
+    pub events: Vec<WebhookEvent>,
+}
+
+impl WebhookConfig {
+    fn default_events() -> Vec<WebhookEvent> {
+        vec![WebhookEvent::RunFinished, WebhookEvent::Regression]
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "snake_case")]
+pub struct ServerConfig {
+    pub address: String,
+    /// Inbound webhooks must be signed with HMAC-SHA256 using this secret
+    pub secret: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: "0.0.0.0:8080".to_owned(),
+            secret: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct ScheduleConfig {
+    pub name: String,
+    /// Cron expression with a leading seconds field, e.g. `0 0 3 * * *` for 03:00 UTC daily
+    pub cron: String,
+    /// Template codes to run on each tick, empty runs the whole catalog
+    #[serde(default)]
+    pub templates: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "snake_case")]
+pub struct DaemonConfig {
+    /// Where the scheduler persists its state across restarts
+    pub state_path: PathBuf,
+    pub schedules: Vec<ScheduleConfig>,
+    /// Serves Prometheus metrics at `/metrics` on this address when set
+    pub metrics_address: Option<String>,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            state_path: PathBuf::from("./output/daemon-state.json"),
+            schedules: Vec::new(),
+            metrics_address: None,
+        }
+    }
+}
+
+/// Forces a protocol probe for a template's services instead of guessing it from the image
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct ProbeConfig {
+    pub template: String,
+    /// Service name within the template, unset applies to all of its services
+    #[serde(default)]
+    pub service: Option<String>,
+    /// One of `tcp`, `postgres`, `redis`, `mysql` or `mongodb`
+    pub protocol: String,
+}
+
+/// Executable run once a template is deployed, for checks smoke tests can't express
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct ScriptConfig {
+    pub template: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Deadlines for each stage of a template run, in seconds
+#[derive(Deserialize, Debug, Copy, Clone)]
+#[serde(default, rename_all = "snake_case")]
+pub struct Timeouts {
+    pub deploy: u64,
+    pub workflow: u64,
+    pub build: u64,
+    pub healthcheck: u64,
+    pub smoke_tests: u64,
+    pub script: u64,
+    /// Writing a sentinel, redeploying and reading it back, for each service with a volume
+    pub persistence: u64,
+    /// Restarting then redeploying every service and checking it again after each
+    pub resilience: u64,
+    pub logs: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            deploy: 5 * 60,
+            workflow: 10 * 60,
+            build: 30 * 60,
+            healthcheck: 10 * 60,
+            smoke_tests: 5 * 60,
+            script: 10 * 60,
+            persistence: 15 * 60,
+            resilience: 20 * 60,
+            logs: 5 * 60,
+        }
+    }
+}
+
+/// Run configuration, read from the JSON file pointed by `CRATER_CONFIG`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "snake_case")]
+pub struct Config {
+    /// Template codes to run, empty runs the whole catalog
+    pub templates: Vec<String>,
+    pub ci_report: CiReportFormat,
+    pub timeouts: Timeouts,
+    /// Seconds every service must stay up after first becoming active, 0 skips the check
+    pub stability_window: u64,
+    /// Restarts then redeploys every service once the first deploy is healthy, catching
+    /// templates that only work on a fresh project
+    pub resilience: bool,
+    pub probes: Vec<ProbeConfig>,
+    /// Directory holding `{code}.json` smoke tests, templates without a file skip the stage
+    pub smoke_tests_dir: PathBuf,
+    pub scripts: Vec<ScriptConfig>,
+    pub webhooks: Vec<WebhookConfig>,
+    pub server: ServerConfig,
+    pub daemon: DaemonConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            templates: ["postgres", "qHvw-4", "redis", "OpUzwe", "strapi"]
+                .into_iter()
+                .map(ToOwned::to_owned)
+                .collect(),
+            ci_report: CiReportFormat::default(),
+            timeouts: Timeouts::default(),
+            stability_window: 2 * 60,
+            resilience: false,
+            probes: Vec::new(),
+            smoke_tests_dir: PathBuf::from("./smoke-tests"),
+            scripts: Vec::new(),
+            webhooks: Vec::new(),
+            server: ServerConfig::default(),
+            daemon: DaemonConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub async fn load() -> Result<Self> {
+        let Ok(path) = std::env::var("CRATER_CONFIG") else {
+            return Ok(Self::default());
+        };
+
+        let path = PathBuf::from(path);
+        let json = tokio::fs::read_to_string(&path).await?;
+        serde_json::from_str(&json).map_err(|err| Error::Config(err, path.display().to_string()))
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{config::ScheduleConfig, metrics, Config, Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path, str::FromStr, sync::Arc, time::Duration};
+use tokio::{sync::Mutex, task::JoinHandle};
+use tracing::{error, info, warn};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ScheduleState {
+    last_tick: Option<DateTime<Utc>>,
+    last_started: Option<DateTime<Utc>>,
+    last_finished: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    skipped: u64,
+}
+
+/// Persisted between restarts so missed ticks and history aren't lost
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DaemonState {
+    schedules: HashMap<String, ScheduleState>,
+}
+
+impl DaemonState {
+    async fn load(path: &Path) -> Self {
+        match tokio::fs::read(path).await {
+            Ok(json) => serde_json::from_slice(&json).unwrap_or_else(|err| {
+                warn!("Invalid daemon state at {}: {err}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // Write then rename so a crash never leaves a truncated state behind
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec_pretty(self)?).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+}
+
+struct Scheduled {
+    config: ScheduleConfig,
+    schedule: cron::Schedule,
+    running: Option<JoinHandle<()>>,
+}
+
+/// Runs the configured schedules forever, handing each tick to [`crate::run`]
+pub async fn daemon(token: String, config: Config) -> Result<()> {
+    if config.daemon.schedules.is_empty() {
+        return Err(Error::MissingConfig("daemon.schedules"));
+    }
+
+    let mut scheduled = Vec::with_capacity(config.daemon.schedules.len());
+    for schedule in &config.daemon.schedules {
+        scheduled.push(Scheduled {
+            config: schedule.clone(),
+            schedule: cron::Schedule::from_str(&schedule.cron)
+                .map_err(|err| Error::Cron(err, schedule.cron.clone()))?,
+            running: None,
+        });
+    }
+
+    if let Some(address) = config.daemon.metrics_address.clone() {
+        tokio::spawn(async move {
+            if let Err(err) = metrics::serve(&address).await {
+                error!("Unable to serve metrics: {err}");
+            }
+        });
+    }
+
+    let state_path = config.daemon.state_path.clone();
+    let state = Arc::new(Mutex::new(DaemonState::load(&state_path).await));
+    let started = Utc::now();
+
+    info!("Daemon started with {} schedules", scheduled.len());
+    loop {
+        let now = Utc::now();
+        let mut wake_at: Option<DateTime<Utc>> = None;
+
+        for scheduled in &mut scheduled {
+            let name = scheduled.config.name.clone();
+            let last_tick = state
+                .lock()
+                .await
+                .schedules
+                .get(&name)
+                .and_then(|s| s.last_tick)
+                .unwrap_or(started);
+
+            // Ticks missed while the daemon was down are coalesced into a single run
+            let due = scheduled
+                .schedule
+                .after(&last_tick)
+                .next()
+                .is_some_and(|next| next <= now);
+
+            if due {
+                let mut guard = state.lock().await;
+                let schedule_state = guard.schedules.entry(name.clone()).or_default();
+                schedule_state.last_tick = Some(now);
+
+                if scheduled.running.as_ref().is_some_and(|r| !r.is_finished()) {
+                    warn!("Skipping tick of {name}, previous run is still active");
+                    schedule_state.skipped += 1;
+                } else {
+                    info!("Starting scheduled run {name}");
+                    schedule_state.last_started = Some(now);
+                    scheduled.running = Some(tokio::spawn(scheduled_run(
+                        token.clone(),
+                        Config {
+                            templates: scheduled.config.templates.clone(),
+                            ..config.clone()
+                        },
+                        name.clone(),
+                        state.clone(),
+                    )));
+                }
+
+                if let Err(err) = guard.save(&state_path).await {
+                    error!("Unable to save daemon state: {err}");
+                }
+            }
+
+            if let Some(next) = scheduled.schedule.after(&now).next() {
+                wake_at = Some(wake_at.map_or(next, |w| w.min(next)));
+            }
+        }
+
+        let Some(wake_at) = wake_at else {
+            warn!("No schedule will ever fire again, stopping daemon");
+            return Ok(());
+        };
+
+        let sleep = (wake_at - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+        tokio::time::sleep(sleep).await;
+    }
+}
+
+async fn scheduled_run(
+    token: String,
+    config: Config,
+    name: String,
+    state: Arc<Mutex<DaemonState>>,
+) {
+    let state_path = config.daemon.state_path.clone();
+    let result = crate::run(token, config).await;
+
+    let mut guard = state.lock().await;
+    let schedule_state = guard.schedules.entry(name.clone()).or_default();
+    schedule_state.last_finished = Some(Utc::now());
+    match result {
+        Ok(summary) => {
+            info!(
+                "Scheduled run {name} finished: {} passed, {} failed",
+                summary.passed(),
+                summary.failed()
+            );
+            schedule_state.last_error = None;
+        }
+        Err(err) => {
+            error!("Scheduled run {name} failed: {err}");
+            schedule_state.last_error = Some(err.to_string());
+        }
+    }
+
+    if let Err(err) = guard.save(&state_path).await {
+        error!("Unable to save daemon state: {err}");
+    }
+}
@@ -1,12 +1,19 @@
+use crate::outcome::StageKind;
 use chrono::{DateTime, Utc};
-use std::num::{ParseFloatError, ParseIntError};
-use tracing::*;
+use std::{
+    num::{ParseFloatError, ParseIntError},
+    time::Duration,
+};
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 #[remain::sorted]
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    #[error("invalid config at {1}: {0}")]
+    Config(serde_json::Error, String),
+    #[error("invalid cron expression {1}: {0}")]
+    Cron(cron::error::Error, String),
     #[error("date out of range: {0} - {1}")]
     DateOutOfRange(DateTime<Utc>, i64),
     #[error("date truncation")]
@@ -15,6 +22,8 @@ pub enum Error {
     DotEnv(#[from] dotenv::Error),
     #[error(transparent)]
     HMacInvalidLength(#[from] hmac::digest::InvalidLength),
+    #[error("invalid address {1}: {0}")]
+    InvalidAddress(std::net::AddrParseError, String),
     #[error("invalid time delta: secs = {0}, nano = {1}")]
     InvalidTimeDelta(i64, i64),
     #[error(transparent)]
@@ -23,28 +32,52 @@ pub enum Error {
     Json(#[from] serde_json::Error),
     #[error("json error: {0} with payload {1:#?}")]
     JsonWithMetadata(serde_json::Error, serde_json::Value),
+    #[error("missing config: {0}")]
+    MissingConfig(&'static str),
     #[error("missing env var: {0}")]
     MissingEnvVar(&'static str),
     #[error("parse int error for {1}: {0}")]
     ParseFloatWithMetadata(ParseFloatError, String),
     #[error("parse int error for {1}: {0}")]
     ParseIntWithMetadata(ParseIntError, String),
+    #[error("{0} probe failed: {1}")]
+    Probe(&'static str, String),
     #[error("railway responded with: {0:?}")]
     Railway(Vec<String>),
     #[error("railway reqwest body error for {1}: {0} ({2:#?})")]
-    RailwayBody(reqwest::Error, &'static str, serde_json::Value),
+    RailwayBody(reqwest::Error, String, serde_json::Value),
     #[error("railway data missing: {0}")]
     RailwayDataMissing(&'static str),
     #[error("railway reqwest failure for {1}: {0} ({2:#?})")]
-    RailwayFailure(reqwest::Error, &'static str, serde_json::Value),
+    RailwayFailure(reqwest::Error, String, serde_json::Value),
     #[error("railway request failed with status {0}: {1}")]
     RailwayStatusFailure(u16, String),
+    #[error("railway operation timed out after {}s", .0.as_secs())]
+    RailwayTimeout(Duration),
+    #[error("http server error: {0}")]
+    Server(String),
+    #[error("invalid smoke tests at {1}: {0}")]
+    SmokeTests(serde_json::Error, String),
+    #[error("subscription error: {0}")]
+    Subscription(String),
+    #[error("unable to export traces: {0}")]
+    Telemetry(#[from] opentelemetry::trace::TraceError),
+    #[error("{stage} timed out after {}s", elapsed.as_secs())]
+    Timeout { stage: StageKind, elapsed: Duration },
     #[error("railway reqwest body error for {1}: {0}")]
     WebHookBody(reqwest::Error, String),
     #[error("webhook reqwest failure for {1}: {0}")]
     WebHookFailure(reqwest::Error, String),
     #[error("webhook request failed with status {0}: {1}")]
     WebHookStatusFailure(u16, String),
+    #[error(transparent)]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
     #[error("{0}")]
     Workflow(String),
 }
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(err))
+    }
+}
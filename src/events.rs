@@ -0,0 +1,66 @@
+use crate::{
+    outcome::{StageKind, TemplateOutcome},
+    DeploymentStatus, RunSummary,
+};
+use std::sync::Arc;
+
+/// Events a lagging subscriber can fall behind by before it misses some
+pub(crate) const CAPACITY: usize = 1024;
+
+/// Progress of a run as it happens, subscribe with [`crate::pipeline::Pipeline::subscribe`]
+#[derive(Debug, Clone)]
+pub enum RunEvent {
+    /// The template was assigned to one of the run's workers, templates of a worker run one at
+    /// a time
+    TemplateQueued {
+        template: String,
+        worker: usize,
+    },
+    StageStarted {
+        template: String,
+        /// Tells apart runs of the same template, what [`crate::pipeline::Controls`] steer
+        run: u64,
+        stage: StageKind,
+    },
+    Deploying {
+        template: String,
+    },
+    /// The project exists from now on, until [`RunEvent::Cleaned`]
+    Deployed {
+        template: String,
+        project_id: String,
+    },
+    /// The workflow creating the project ended, `error` is set when it failed
+    WorkflowComplete {
+        template: String,
+        project_id: String,
+        error: Option<String>,
+    },
+    /// A service's latest deployment moved to `status`, terminal or not
+    BuildStatusChanged {
+        template: String,
+        service: String,
+        deployment_id: Option<String>,
+        status: DeploymentStatus,
+    },
+    /// An HTTP healthcheck or TCP probe finished, `target` being its URL or address
+    HealthcheckResult {
+        template: String,
+        service: String,
+        target: String,
+        healthy: bool,
+        attempts: u64,
+    },
+    /// The project was deleted, or failed to be when `error` is set
+    Cleaned {
+        template: String,
+        project_id: String,
+        error: Option<String>,
+    },
+    TemplateFinished {
+        outcome: Arc<TemplateOutcome>,
+    },
+    RunFinished {
+        summary: RunSummary,
+    },
+}
@@ -0,0 +1,61 @@
+use crate::outcome::HealthcheckOutcome;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct Healthcheck;
+
+impl Healthcheck {
+    /// Polls `url` until it answers with a 2xx or `timeout` elapses
+    pub async fn check(url: String, timeout: Duration) -> HealthcheckOutcome {
+        let client = reqwest::Client::new();
+        let deadline = Instant::now() + timeout;
+
+        let mut outcome = HealthcheckOutcome {
+            url,
+            status_code: None,
+            attempts: 0,
+            latency: None,
+            error: None,
+        };
+
+        let mut interval = tokio::time::interval(RETRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            outcome.attempts += 1;
+
+            let started = Instant::now();
+            match client
+                .get(&outcome.url)
+                .timeout(RETRY_INTERVAL)
+                .send()
+                .await
+            {
+                Ok(response) => {
+                    outcome.status_code = Some(response.status().as_u16());
+                    outcome.latency = Some(started.elapsed());
+                    outcome.error = None;
+                }
+                Err(err) => {
+                    debug!("Healthcheck for {} failed: {err}", outcome.url);
+                    outcome.status_code = err.status().map(|s| s.as_u16());
+                    outcome.latency = None;
+                    outcome.error = Some(err.to_string());
+                }
+            }
+
+            if outcome.is_healthy() {
+                return outcome;
+            }
+
+            if Instant::now() >= deadline {
+                warn!(
+                    "Healthcheck for {} never succeeded after {} attempts",
+                    outcome.url, outcome.attempts
+                );
+                return outcome;
+            }
+        }
+    }
+}
@@ -1,36 +1,79 @@
+mod build_logs;
+mod config;
+mod daemon;
 mod environment;
 mod error;
-mod railway;
-
+pub mod events;
+mod healthcheck;
+mod metrics;
+mod notify;
+pub mod outcome;
+mod persistence;
+pub mod pipeline;
+mod probe;
+pub mod railway;
+mod report;
+mod resilience;
+mod script;
+mod server;
+mod signature;
+mod smoke;
+mod stability;
+mod telemetry;
+mod tui;
+
+pub use config::{
+    CiReportFormat, Config, DaemonConfig, ProbeConfig, ScheduleConfig, ScriptConfig, ServerConfig,
+    Timeouts, WebhookConfig,
+};
+pub use daemon::daemon;
 pub use error::{Error, Result};
-
-use crate::environment::{DeserializedEnvironment, DeserializedServiceSource};
-pub(crate) use crate::railway::{
-    deployment::{Deployment, DeploymentLog},
+pub use events::RunEvent;
+pub use outcome::{RunSummary, StageStatus, TemplateSummary};
+pub use server::serve;
+pub use telemetry::Telemetry;
+pub use tui::{tui, TuiLogs};
+
+use crate::notify::{Notification, Notifier};
+use crate::outcome::{StageKind, TemplateOutcome};
+use crate::pipeline::{Context, Pipeline};
+pub use crate::railway::{
+    deployment::{Deployment, DeploymentLog, DeploymentStatus, DeploymentTimeline, Severity},
     project::Project,
-    service::Service,
-    template::{NewService, NewVolume, Template},
+    service::{DomainKind, Networking, Service},
+    template::{DeployedTemplate, NewService, NewVolume, Template},
     workflow::{Workflow, WorkflowStatus},
     Railway,
 };
 
 use chrono::Utc;
 use rand::{prelude::*, thread_rng};
-use serde::Deserialize;
-use std::{collections::HashMap, path::PathBuf, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use tokio::task::JoinSet;
-use tracing::{error, info, warn};
+use tracing::{error, field, info, instrument, warn, Instrument, Span};
+
+const OUTPUT_DIR: &str = "./output";
 
-pub async fn run(token: String) -> Result<()> {
+pub async fn run(token: String, config: Config) -> Result<RunSummary> {
+    run_with(token, config, Pipeline::default()).await
+}
+
+/// Runs every template through `pipeline` rather than crater's own stages
+#[instrument(name = "run", skip_all, fields(templates = field::Empty))]
+pub async fn run_with(token: String, config: Config, pipeline: Pipeline) -> Result<RunSummary> {
+    let started_at = Utc::now();
     let mut templates: Vec<_> = Template::list(&token)
         .await?
         .into_iter()
-        .filter(|t| {
-            vec!["postgres", "qHvw-4", "redis", "OpUzwe", "strapi"].contains(&t.code().as_str())
-        })
+        .filter(|t| config.templates.is_empty() || config.templates.contains(t.code()))
         .collect();
     templates.shuffle(&mut thread_rng());
     info!("Templates: {}", templates.len());
+    Span::current().record("templates", templates.len());
 
     let (first_chunk, third_chunk) = templates.split_at(templates.len() / 2);
     let (first_chunk, second_chunk) = first_chunk.split_at(first_chunk.len() / 2);
@@ -41,14 +84,61 @@ pub async fn run(token: String) -> Result<()> {
     let third_chunk = third_chunk.to_vec();
     let fourth_chunk = fourth_chunk.to_vec();
 
-    let dir = PathBuf::from(format!("./output/crater-run-{}", Utc::now()));
+    let dir = PathBuf::from(format!("{OUTPUT_DIR}/crater-run-{started_at}"));
     tokio::fs::create_dir_all(&dir).await?;
 
+    let chunks = [&first_chunk, &second_chunk, &third_chunk, &fourth_chunk];
+    for (worker, chunk) in chunks.into_iter().enumerate() {
+        for template in chunk {
+            pipeline.emit(RunEvent::TemplateQueued {
+                template: template.code().clone(),
+                worker,
+            });
+        }
+    }
+
+    let pipeline = Arc::new(pipeline);
     let mut tasks = JoinSet::new();
-    tasks.spawn(run_each(dir.clone(), token.clone(), first_chunk));
-    tasks.spawn(run_each(dir.clone(), token.clone(), second_chunk));
-    tasks.spawn(run_each(dir.clone(), token.clone(), third_chunk));
-    tasks.spawn(run_each(dir.clone(), token.clone(), fourth_chunk));
+    tasks.spawn(
+        run_each(
+            dir.clone(),
+            token.clone(),
+            config.clone(),
+            first_chunk,
+            pipeline.clone(),
+        )
+        .in_current_span(),
+    );
+    tasks.spawn(
+        run_each(
+            dir.clone(),
+            token.clone(),
+            config.clone(),
+            second_chunk,
+            pipeline.clone(),
+        )
+        .in_current_span(),
+    );
+    tasks.spawn(
+        run_each(
+            dir.clone(),
+            token.clone(),
+            config.clone(),
+            third_chunk,
+            pipeline.clone(),
+        )
+        .in_current_span(),
+    );
+    tasks.spawn(
+        run_each(
+            dir.clone(),
+            token.clone(),
+            config.clone(),
+            fourth_chunk,
+            pipeline.clone(),
+        )
+        .in_current_span(),
+    );
 
     let mut results = Vec::new();
 
@@ -63,14 +153,94 @@ pub async fn run(token: String) -> Result<()> {
 
     let run = results.into_iter().fold(Run::default(), |mut acc, run| {
         acc.total += run.total;
+        acc.healthy += run.healthy;
         acc.valid += run.valid;
         acc.errors.extend(run.errors);
+        acc.outcomes.extend(run.outcomes);
         acc
     });
 
-    info!("Run: {run:#?}");
+    info!(
+        "Run: {} templates, {} valid, {} healthy, {} errors",
+        run.total,
+        run.valid,
+        run.healthy,
+        run.errors.len()
+    );
+
+    report::Html::write(&dir, &run.outcomes).await?;
+    info!("Report written to {}", dir.join("index.html").display());
+
+    match config.ci_report {
+        CiReportFormat::Junit => report::Junit::write(&dir, &run.outcomes).await?,
+        CiReportFormat::Tap => report::Tap::write(&dir, &run.outcomes).await?,
+        CiReportFormat::None => {}
+    }
+
+    let summary = RunSummary::new(started_at, &run.outcomes);
+    let previous = previous_summary(&dir).await;
+    tokio::fs::write(dir.join("summary.json"), serde_json::to_vec(&summary)?).await?;
+    pipeline.emit(RunEvent::RunFinished {
+        summary: summary.clone(),
+    });
+
+    if !config.webhooks.is_empty() {
+        let notifier = Notifier::new(config.webhooks.clone());
+        let report = dir.join("index.html").display().to_string();
+
+        let mut notifications = Vec::new();
+        if let Some(previous) = previous {
+            let regressions = summary.regressions(&previous);
+            if !regressions.is_empty() {
+                warn!("Detected {} regressions", regressions.len());
+                notifications.push(Notification::Regression {
+                    summary: summary.clone(),
+                    regressions,
+                    report: report.clone(),
+                });
+            }
+        }
+        notifications.push(Notification::RunFinished {
+            summary: summary.clone(),
+            report,
+        });
+
+        for notification in &notifications {
+            for err in notifier.notify(notification).await {
+                error!("Unable to send webhook: {err}");
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Summary of the most recent run before `current`, if any finished
+async fn previous_summary(current: &Path) -> Option<RunSummary> {
+    let mut entries = tokio::fs::read_dir(OUTPUT_DIR).await.ok()?;
+    let mut dirs = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let is_run = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("crater-run-"));
+        if is_run && path.file_name() != current.file_name() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
 
-    Ok(())
+    for dir in dirs.into_iter().rev() {
+        let Ok(json) = tokio::fs::read(dir.join("summary.json")).await else {
+            continue;
+        };
+        match serde_json::from_slice(&json) {
+            Ok(summary) => return Some(summary),
+            Err(err) => warn!("Invalid summary at {}: {err}", dir.display()),
+        }
+    }
+    None
 }
 
 #[derive(Default, Debug)]
@@ -79,283 +249,39 @@ struct Run {
     healthy: u64,
     valid: u64,
     errors: Vec<Box<dyn std::error::Error + Sync + Send>>,
+    outcomes: Vec<TemplateOutcome>,
 }
 
-async fn run_each(dir: PathBuf, token: String, chunk: Vec<Template>) -> Run {
-    let mut run = Run {
-        total: 0,
-        healthy: 0,
-        valid: 0,
-        errors: Vec::new(),
-    };
+async fn run_each(
+    dir: PathBuf,
+    token: String,
+    config: Config,
+    chunk: Vec<Template>,
+    pipeline: Arc<Pipeline>,
+) -> Run {
+    let mut run = Run::default();
 
     let mut interval = tokio::time::interval(Duration::from_secs(1));
 
-    'outer: for template in chunk {
+    for template in chunk {
         interval.tick().await;
 
         run.total += 1;
 
-        if template.serialized_config().is_null() {
-            warn!("No serialized config for {}, skipping it", template.code());
-            continue;
+        let mut context = Context::new(dir.clone(), token.clone(), config.clone(), template);
+        pipeline.run(&mut context).await;
+        let (outcome, errors) = context.finish();
+        run.errors.extend(errors);
+        if outcome.stage(&StageKind::Workflow).map(|s| s.status()) == Some(StageStatus::Passed) {
+            run.valid += 1;
         }
-
-        let config =
-            match Option::<DeserializedEnvironment>::deserialize(template.serialized_config()) {
-                Ok(config) => config,
-                Err(err) => {
-                    error!(
-                        "Unable to deserialize services for template {}: {err}",
-                        template.code()
-                    );
-                    run.errors.push(Box::new(err));
-                    continue;
-                }
-            };
-
-        let mut services = Vec::new();
-
-        let mut any_healthcheck = false;
-        for (id, service) in config.as_ref().map_or(&HashMap::new(), |c| c.services()) {
-            let mut variables = HashMap::new();
-            for (name, variable) in service.variables() {
-                variables.insert("RAILWAY_BETA_ENABLE_BUILD_V2".to_owned(), "1".to_owned());
-
-                if let Some(value) = variable.default_value().clone().filter(|v| !v.is_empty()) {
-                    variables.insert(name.clone(), value);
-                } else if !variable.is_optional().unwrap_or_default() {
-                    warn!("Missing env var {name} for template {}", template.code());
-                    continue 'outer;
-                }
-            }
-
-            let volumes = service
-                .volume_mounts()
-                .values()
-                .map(|volume| NewVolume {
-                    mount_path: volume.mount_path().clone(),
-                })
-                .collect();
-
-            if service
-                .deploy()
-                .as_ref()
-                .and_then(|d| d.healthcheck_path().clone())
-                .is_some()
-            {
-                any_healthcheck = true;
-            }
-
-            services.push(NewService {
-                id: id.clone(),
-                has_domain: service
-                    .networking()
-                    .as_ref()
-                    .map(|n| !n.service_domains().is_empty()),
-                healthcheck_path: service
-                    .deploy()
-                    .as_ref()
-                    .and_then(|d| d.healthcheck_path().clone()),
-                name: service.name().clone(),
-                root_directory: match service.source() {
-                    Some(DeserializedServiceSource::Image { .. }) => None,
-                    Some(DeserializedServiceSource::Repo { root_directory, .. }) => {
-                        root_directory.clone()
-                    }
-                    None => None,
-                },
-                service_icon: service.icon().clone(),
-                service_name: service.name().clone(),
-                start_command: service
-                    .deploy()
-                    .as_ref()
-                    .and_then(|d| d.start_command().clone()),
-                tcp_proxy_application_port: match service
-                    .networking()
-                    .as_ref()
-                    .and_then(|n| n.tcp_proxies().keys().next().map(|k| k.parse::<i64>()))
-                    .transpose()
-                {
-                    Ok(p) => p,
-                    Err(err) => {
-                        error!(
-                            "Invalid tcp procy application port for template {}: {err}",
-                            template.code()
-                        );
-                        run.errors.push(Box::new(err));
-                        continue 'outer;
-                    }
-                },
-                template: match service.source() {
-                    Some(DeserializedServiceSource::Image { image }) => image.clone(),
-                    Some(DeserializedServiceSource::Repo { repo, .. }) => repo.clone(),
-                    None => service.name().clone(),
-                },
-                variables,
-                volumes,
-            });
+        if outcome
+            .stage(&StageKind::Healthcheck)
+            .is_some_and(|s| matches!(s.status(), StageStatus::Passed | StageStatus::Flaky))
+        {
+            run.healthy += 1;
         }
-
-        info!("Deploying {}", template.code());
-        let deployed = match Template::deploy(&token, services, template.code()).await {
-            Ok(deployed) => deployed,
-            Err(err) => {
-                error!("Unable to deploy template {}: {err}", template.code());
-                run.errors.push(Box::new(err));
-                continue;
-            }
-        };
-        info!("Checking workflow for {}", template.code());
-
-        let status = if let Some(id) = deployed.workflow_id() {
-            match Workflow::status(&token, id).await {
-                Ok(status) => status,
-                Err(err) => {
-                    error!(
-                        "Discarded Template {} because of error: {err}",
-                        template.code()
-                    );
-                    run.errors.push(Box::new(err));
-
-                    if let Err(err) = Project::delete(&token, deployed.project_id()).await {
-                        error!(
-                            "Unable to delete project {} for template {}: {err}",
-                            deployed.project_id(),
-                            template.code()
-                        );
-                        run.errors.push(Box::new(err));
-                    }
-                    continue;
-                }
-            }
-        } else {
-            error!("No workflow id for {}", template.code());
-
-            if let Err(err) = Project::delete(&token, deployed.project_id()).await {
-                error!("Unable to delete project {}: {err}", deployed.project_id());
-                run.errors.push(Box::new(err));
-            }
-            continue;
-        };
-
-        if let WorkflowStatus::Error(err) = status {
-            error!("Unable to process {}: {err}", template.code());
-            run.errors.push(Box::new(Error::Workflow(err)));
-
-            if let Err(err) = Project::delete(&token, deployed.project_id()).await {
-                error!("Unable to delete project {}: {err}", deployed.project_id());
-                run.errors.push(Box::new(err));
-            }
-            continue;
-        }
-
-        run.valid += 1;
-
-        info!("Waiting for all builds: {}", template.code());
-        if let Err(err) = Service::wait_for_all_builds(&token, deployed.project_id()).await {
-            error!(
-                "Unable to wait for all builds for {}: {err}",
-                template.code()
-            );
-            run.errors.push(Box::new(err));
-
-            if let Err(err) = Project::delete(&token, deployed.project_id()).await {
-                error!("Unable to delete project {}: {err}", deployed.project_id());
-                run.errors.push(Box::new(err));
-            }
-            continue;
-        }
-
-        /*
-        if dbg!(any_healthcheck) {
-            // TODO: check healthcheck
-            /*
-            let healthcheck = todo!();
-            if healthcheck {
-                let full_path = format!("full_path/{healthcheck}");
-                reqwest::get(healthcheck)
-            }
-            */
-            tokio::time::sleep(Duration::from_secs(60)).await;
-        } else {
-            tokio::time::sleep(Duration::from_secs(60)).await;
-        }
-        */
-
-        info!("Listing services");
-        let services = match Service::list(&token, deployed.project_id()).await {
-            Ok(services) => services,
-            Err(err) => {
-                run.errors.push(Box::new(err));
-
-                if let Err(err) = Project::delete(&token, deployed.project_id()).await {
-                    error!("Unable to delete project {}: {err}", deployed.project_id());
-                    run.errors.push(Box::new(err));
-                }
-                continue;
-            }
-        };
-
-        for service in &services {
-            for instance in service.instances() {
-                if let Some(deployment_id) = instance.deployment_id() {
-                    let build_logs = match Deployment::build_logs(&token, deployment_id).await {
-                        Ok(logs) => logs,
-                        Err(err) => {
-                            error!("Unable to fetch build logs: {err}");
-                            run.errors.push(Box::new(err));
-
-                            if let Err(err) = Project::delete(&token, deployed.project_id()).await {
-                                error!("Unable to delete project {}: {err}", deployed.project_id());
-                                run.errors.push(Box::new(err));
-                            }
-                            continue;
-                        }
-                    };
-
-                    let json = match serde_json::to_string(&build_logs) {
-                        Ok(json) => json,
-                        Err(err) => {
-                            error!("Unable to serialize build logs: {err}");
-                            run.errors.push(Box::new(err));
-
-                            if let Err(err) = Project::delete(&token, deployed.project_id()).await {
-                                error!("Unable to delete project {}: {err}", deployed.project_id());
-                                run.errors.push(Box::new(err));
-                            }
-                            continue;
-                        }
-                    };
-
-                    if let Err(err) = tokio::fs::write(
-                        dir.join(format!("{}-{}.json", template.code(), service.name())),
-                        &json,
-                    )
-                    .await
-                    {
-                        error!("Unable to serialize build logs: {err}");
-                        run.errors.push(Box::new(err));
-
-                        if let Err(err) = Project::delete(&token, deployed.project_id()).await {
-                            error!("Unable to delete project {}: {err}", deployed.project_id());
-                            run.errors.push(Box::new(err));
-                        }
-                        continue;
-                    }
-
-                    // TODO: collect deployment logs
-                }
-            }
-        }
-
-        if let Err(err) = Project::delete(&token, deployed.project_id()).await {
-            error!("Unable to delete project {}: {err}", deployed.project_id());
-            run.errors.push(Box::new(err));
-            continue;
-        }
-
-        info!("Processed template: {}", template.code());
+        run.outcomes.push(outcome);
     }
 
     run
@@ -1,4 +1,4 @@
-use crater::Error;
+use crater::{Config, Error, Telemetry, TuiLogs};
 
 use tracing_subscriber::prelude::*;
 
@@ -25,16 +25,39 @@ async fn main() -> color_eyre::Result<()> {
         std::env::set_var("RUST_LOG", val);
     }
 
+    let mode = std::env::args().nth(1);
+    // The dashboard owns the terminal, logs go to its own pane
+    let logs = TuiLogs::default();
+    let tui = mode.as_deref() == Some("tui");
+    // Flushes the spans still batched when main returns
+    let telemetry = Telemetry::from_env()?;
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
             std::env::var("RUST_LOG").unwrap_or_else(|_| "crater=info".into()),
         ))
-        .with(tracing_subscriber::fmt::layer())
+        .with((!tui).then(tracing_subscriber::fmt::layer))
+        .with(tui.then(|| {
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(logs.clone())
+        }))
+        .with(telemetry.as_ref().map(Telemetry::layer))
         .init();
 
     let token = std::env::var("RAILWAY_API_TOKEN")
         .map_err(|_| Error::MissingEnvVar("RAILWAY_API_TOKEN"))?;
-    crater::run(token).await?;
+    let config = Config::load().await?;
+    match mode.as_deref() {
+        Some("serve") => crater::serve(token, config).await?,
+        Some("daemon") => crater::daemon(token, config).await?,
+        Some("tui") => {
+            crater::tui(token, config, logs).await?;
+        }
+        Some(mode) => return Err(color_eyre::eyre::eyre!("unknown mode: {mode}")),
+        None => {
+            crater::run(token, config).await?;
+        }
+    }
 
     Ok(())
 }
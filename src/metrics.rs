@@ -0,0 +1,210 @@
+use crate::{outcome::TemplateOutcome, Error, Result, RunEvent};
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use std::{net::SocketAddr, sync::LazyLock, time::Duration};
+use tracing::info;
+
+/// Stages take from seconds to the better part of an hour for slow builds
+const STAGE_BUCKETS: &[f64] = &[
+    1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0,
+];
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Everything exposed at `/metrics`, recorded whichever mode crater runs in
+struct Metrics {
+    registry: Registry,
+    templates: IntCounterVec,
+    template_failures: IntCounterVec,
+    stages: IntCounterVec,
+    stage_duration: HistogramVec,
+    railway_requests: IntCounterVec,
+    railway_errors: IntCounterVec,
+    railway_duration: HistogramVec,
+    live_projects: IntGauge,
+    cleanup_failures: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let templates = IntCounterVec::new(
+            Opts::new("crater_templates_total", "Templates run, by their status"),
+            &["template", "status"],
+        )
+        .expect("valid metric");
+        let template_failures = IntCounterVec::new(
+            Opts::new(
+                "crater_template_failures_total",
+                "Failed templates, by the stage they failed at",
+            ),
+            &["template", "stage"],
+        )
+        .expect("valid metric");
+        let stages = IntCounterVec::new(
+            Opts::new("crater_stages_total", "Stages run, by their status"),
+            &["stage", "status"],
+        )
+        .expect("valid metric");
+        let stage_duration = HistogramVec::new(
+            HistogramOpts::new("crater_stage_duration_seconds", "Time spent in each stage")
+                .buckets(STAGE_BUCKETS.to_vec()),
+            &["stage"],
+        )
+        .expect("valid metric");
+        let railway_requests = IntCounterVec::new(
+            Opts::new(
+                "crater_railway_requests_total",
+                "Railway API requests, by GraphQL operation",
+            ),
+            &["operation"],
+        )
+        .expect("valid metric");
+        let railway_errors = IntCounterVec::new(
+            Opts::new(
+                "crater_railway_request_errors_total",
+                "Railway API requests that failed, by GraphQL operation",
+            ),
+            &["operation"],
+        )
+        .expect("valid metric");
+        let railway_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "crater_railway_request_duration_seconds",
+                "Railway API request latency, by GraphQL operation",
+            ),
+            &["operation"],
+        )
+        .expect("valid metric");
+        let live_projects = IntGauge::new(
+            "crater_live_projects",
+            "Projects deployed and not deleted yet",
+        )
+        .expect("valid metric");
+        let cleanup_failures = IntCounterVec::new(
+            Opts::new(
+                "crater_cleanup_failures_total",
+                "Projects that failed to be deleted, they have to be deleted by hand",
+            ),
+            &["template"],
+        )
+        .expect("valid metric");
+
+        let registry = Registry::new();
+        for collector in [
+            Box::new(templates.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(template_failures.clone()),
+            Box::new(stages.clone()),
+            Box::new(stage_duration.clone()),
+            Box::new(railway_requests.clone()),
+            Box::new(railway_errors.clone()),
+            Box::new(railway_duration.clone()),
+            Box::new(live_projects.clone()),
+            Box::new(cleanup_failures.clone()),
+        ] {
+            registry.register(collector).expect("metrics are unique");
+        }
+
+        Self {
+            registry,
+            templates,
+            template_failures,
+            stages,
+            stage_duration,
+            railway_requests,
+            railway_errors,
+            railway_duration,
+            live_projects,
+            cleanup_failures,
+        }
+    }
+}
+
+/// Counts the template and its stages once it's done
+pub(crate) fn record_template(outcome: &TemplateOutcome) {
+    let metrics = &*METRICS;
+    let status = outcome.status().to_string();
+    metrics
+        .templates
+        .with_label_values(&[outcome.code(), &status])
+        .inc();
+    if let Some(failure) = outcome.failure() {
+        metrics
+            .template_failures
+            .with_label_values(&[outcome.code(), &failure.stage().to_string()])
+            .inc();
+    }
+
+    for stage in outcome.stages() {
+        let kind = stage.stage().to_string();
+        metrics
+            .stages
+            .with_label_values(&[&kind, &stage.status().to_string()])
+            .inc();
+        metrics
+            .stage_duration
+            .with_label_values(&[&kind])
+            .observe(stage.duration().as_secs_f64());
+    }
+}
+
+/// Tracks live projects from the events of a running template
+pub(crate) fn observe(event: &RunEvent) {
+    let metrics = &*METRICS;
+    match event {
+        RunEvent::Deployed { .. } => metrics.live_projects.inc(),
+        RunEvent::Cleaned { error: None, .. } => metrics.live_projects.dec(),
+        RunEvent::Cleaned {
+            template,
+            error: Some(_),
+            ..
+        } => metrics
+            .cleanup_failures
+            .with_label_values(&[template])
+            .inc(),
+        _ => {}
+    }
+}
+
+/// Counts a Railway API request, `operation` being the name its GraphQL document gives it
+pub(crate) fn record_railway_request(operation: &str, duration: Duration, failed: bool) {
+    let metrics = &*METRICS;
+    metrics
+        .railway_requests
+        .with_label_values(&[operation])
+        .inc();
+    metrics
+        .railway_duration
+        .with_label_values(&[operation])
+        .observe(duration.as_secs_f64());
+    if failed {
+        metrics.railway_errors.with_label_values(&[operation]).inc();
+    }
+}
+
+/// Every metric in Prometheus' text format
+pub(crate) async fn handler() -> impl IntoResponse {
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    // Encoding into a Vec only fails on metrics with invalid names, which are fixed here
+    let _ = encoder.encode(&METRICS.registry.gather(), &mut body);
+    (
+        [(header::CONTENT_TYPE, encoder.format_type().to_owned())],
+        body,
+    )
+}
+
+/// Serves `/metrics` on `address` until the process exits
+pub(crate) async fn serve(address: &str) -> Result<()> {
+    let address: SocketAddr = address
+        .parse()
+        .map_err(|err| Error::InvalidAddress(err, address.to_owned()))?;
+    let app = Router::new().route("/metrics", get(handler));
+
+    info!("Serving metrics on {address}");
+    axum::Server::bind(&address)
+        .serve(app.into_make_service())
+        .await
+        .map_err(|err| Error::Server(err.to_string()))
+}
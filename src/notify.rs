@@ -0,0 +1,196 @@
+use crate::{
+    config::{WebhookConfig, WebhookEvent, WebhookFormat},
+    outcome::{RunSummary, TemplateSummary},
+    signature, Error, Result,
+};
+use serde::Serialize;
+use std::{fmt::Write, time::Duration};
+use tracing::{info, warn};
+
+const MAX_ATTEMPTS: u32 = 4;
+const EVENT_HEADER: &str = "X-Crater-Event";
+/// Discord rejects messages longer than this
+const DISCORD_MAX_CONTENT: usize = 2000;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    RunFinished {
+        summary: RunSummary,
+        report: String,
+    },
+    Regression {
+        summary: RunSummary,
+        regressions: Vec<TemplateSummary>,
+        report: String,
+    },
+}
+
+impl Notification {
+    fn event(&self) -> WebhookEvent {
+        match self {
+            Self::RunFinished { .. } => WebhookEvent::RunFinished,
+            Self::Regression { .. } => WebhookEvent::Regression,
+        }
+    }
+
+    fn event_name(&self) -> &'static str {
+        match self {
+            Self::RunFinished { .. } => "run_finished",
+            Self::Regression { .. } => "regression",
+        }
+    }
+
+    /// Human readable text for chat integrations
+    fn text(&self) -> String {
+        let mut text = String::new();
+        match self {
+            Self::RunFinished { summary, report } => {
+                let _ = writeln!(
+                    text,
+                    "Crater run finished in {}s: {} templates, {} passed, {} flaky, {} failed, {} timed out, {} skipped",
+                    summary.duration().as_secs(),
+                    summary.total(),
+                    summary.passed(),
+                    summary.flaky(),
+                    summary.failed(),
+                    summary.timed_out(),
+                    summary.skipped(),
+                );
+                for template in summary.templates() {
+                    if let Some(failure) = template.failure() {
+                        let _ = writeln!(text, "• `{}`: {failure}", template.code());
+                    }
+                }
+                let _ = write!(text, "Report: {report}");
+            }
+            Self::Regression {
+                regressions,
+                report,
+                ..
+            } => {
+                let _ = writeln!(
+                    text,
+                    "Crater detected {} regression(s) since the previous run:",
+                    regressions.len()
+                );
+                for template in regressions {
+                    let _ = writeln!(
+                        text,
+                        "• `{}`: {}",
+                        template.code(),
+                        template.failure().as_deref().unwrap_or("failed")
+                    );
+                }
+                let _ = write!(text, "Report: {report}");
+            }
+        }
+        text
+    }
+
+    fn payload(&self, format: WebhookFormat) -> Result<serde_json::Value> {
+        Ok(match format {
+            WebhookFormat::Json => serde_json::to_value(self)?,
+            WebhookFormat::Slack => serde_json::json!({ "text": self.text() }),
+            WebhookFormat::Discord => {
+                let mut content = self.text();
+                if content.len() > DISCORD_MAX_CONTENT {
+                    let mut end = DISCORD_MAX_CONTENT - 1;
+                    while !content.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    content.truncate(end);
+                    content.push('…');
+                }
+                serde_json::json!({ "content": content })
+            }
+        })
+    }
+}
+
+/// POSTs run notifications to the configured webhooks
+pub struct Notifier {
+    client: reqwest::Client,
+    webhooks: Vec<WebhookConfig>,
+}
+
+impl Notifier {
+    pub fn new(webhooks: Vec<WebhookConfig>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            webhooks,
+        }
+    }
+
+    /// Sends the notification to every interested webhook, returning the ones that failed
+    pub async fn notify(&self, notification: &Notification) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for webhook in &self.webhooks {
+            if !webhook.events.contains(&notification.event()) {
+                continue;
+            }
+
+            match self.send(webhook, notification).await {
+                Ok(()) => info!("Notified {} of {}", webhook.url, notification.event_name()),
+                Err(err) => errors.push(err),
+            }
+        }
+        errors
+    }
+
+    async fn send(&self, webhook: &WebhookConfig, notification: &Notification) -> Result<()> {
+        let body = serde_json::to_vec(&notification.payload(webhook.format)?)?;
+        let signed = webhook
+            .secret
+            .as_deref()
+            .map(|secret| signature::sign(secret.as_bytes(), &body))
+            .transpose()?;
+
+        let mut attempt = 1;
+        loop {
+            let mut request = self
+                .client
+                .post(&webhook.url)
+                .header("Content-Type", "application/json")
+                .header(EVENT_HEADER, notification.event_name())
+                .body(body.clone());
+            if let Some(signed) = &signed {
+                request =
+                    request.header(signature::HEADER, format!("{}{signed}", signature::PREFIX));
+            }
+
+            let result = match request.send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    if status.is_success() {
+                        return Ok(());
+                    }
+
+                    let body = response
+                        .text()
+                        .await
+                        .map_err(|err| Error::WebHookBody(err, webhook.url.clone()))?;
+                    let err = Error::WebHookStatusFailure(status.as_u16(), body);
+                    if !status.is_server_error() && status != 429 {
+                        return Err(err);
+                    }
+                    err
+                }
+                Err(err) => Error::WebHookFailure(err, webhook.url.clone()),
+            };
+
+            if attempt >= MAX_ATTEMPTS {
+                return Err(result);
+            }
+
+            let backoff = Duration::from_secs(1 << (attempt - 1));
+            warn!(
+                "Webhook {} failed (attempt {attempt}/{MAX_ATTEMPTS}), retrying in {}s: {result}",
+                webhook.url,
+                backoff.as_secs()
+            );
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+}
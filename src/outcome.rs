@@ -0,0 +1,476 @@
+use crate::{DeploymentLog, DeploymentStatus, DeploymentTimeline, Error, Networking};
+use chrono::{DateTime, Utc};
+use derive_get::Getters;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Identifies the stage a [`StageOutcome`] was recorded for
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, strum::Display, strum::EnumIter,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum StageKind {
+    Config,
+    Deploy,
+    Workflow,
+    Build,
+    Healthcheck,
+    SmokeTests,
+    Script,
+    Stability,
+    Persistence,
+    Resilience,
+    Logs,
+    Cleanup,
+    /// Stage added to a [`crate::pipeline::Pipeline`] by a library user
+    #[strum(to_string = "{0}")]
+    Custom(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum StageStatus {
+    Passed,
+    /// Passed, but only after more than one attempt
+    Flaky,
+    Failed,
+    /// Failed because the stage exceeded its deadline
+    TimedOut,
+    Skipped,
+}
+
+impl StageStatus {
+    pub fn is_failure(self) -> bool {
+        matches!(self, Self::Failed | Self::TimedOut)
+    }
+
+    pub fn from_error(err: &Error) -> Self {
+        if matches!(err, Error::Timeout { .. }) {
+            Self::TimedOut
+        } else {
+            Self::Failed
+        }
+    }
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct StageOutcome {
+    stage: StageKind,
+    #[copy]
+    status: StageStatus,
+    started_at: DateTime<Utc>,
+    #[copy]
+    duration: Duration,
+    error: Option<String>,
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct HealthcheckOutcome {
+    pub url: String,
+    pub status_code: Option<u16>,
+    #[copy]
+    pub attempts: u64,
+    #[copy]
+    pub latency: Option<Duration>,
+    pub error: Option<String>,
+}
+
+impl HealthcheckOutcome {
+    pub fn is_healthy(&self) -> bool {
+        self.status_code
+            .is_some_and(|code| (200..300).contains(&code))
+    }
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct AssertionOutcome {
+    pub description: String,
+    #[copy]
+    pub passed: bool,
+    pub actual: Option<String>,
+}
+
+/// Request from a template's smoke tests and every assertion made on its response
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct SmokeTestOutcome {
+    pub name: String,
+    pub method: String,
+    pub url: String,
+    #[copy]
+    pub status_code: Option<u16>,
+    #[copy]
+    pub latency: Option<Duration>,
+    pub assertions: Vec<AssertionOutcome>,
+    /// Set when no response was received to assert on
+    pub error: Option<String>,
+}
+
+impl SmokeTestOutcome {
+    pub fn is_passed(&self) -> bool {
+        self.error.is_none() && self.assertions.iter().all(|a| a.passed)
+    }
+}
+
+/// Exit and output of a template's check script
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct ScriptOutcome {
+    pub command: String,
+    /// `None` when killed by a signal
+    #[copy]
+    pub exit_code: Option<i32>,
+    /// Killed when the stage timed out, the output being what it wrote until then
+    #[copy]
+    #[serde(default)]
+    pub timed_out: bool,
+    #[copy]
+    pub duration: Duration,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl ScriptOutcome {
+    pub fn is_passed(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Connectivity check of a non-HTTP endpoint, such as a TCP proxy
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct ProbeOutcome {
+    pub protocol: String,
+    pub address: String,
+    #[copy]
+    pub attempts: u64,
+    /// Only set once the probe succeeded
+    #[copy]
+    pub latency: Option<Duration>,
+    pub error: Option<String>,
+}
+
+impl ProbeOutcome {
+    pub fn is_healthy(&self) -> bool {
+        self.latency.is_some() && self.error.is_none()
+    }
+
+    pub fn target(&self) -> String {
+        format!("{}://{}", self.protocol, self.address)
+    }
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct CrashOutcome {
+    pub at: DateTime<Utc>,
+    pub status: DeploymentStatus,
+    /// Deploy logs from shortly before the crash until shortly after it
+    pub logs: Vec<DeploymentLog>,
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct StabilityOutcome {
+    #[copy]
+    pub window: Duration,
+    pub active_at: Option<DateTime<Utc>>,
+    /// Times the deployment came back up after crashing within the window
+    #[copy]
+    pub restarts: usize,
+    pub crashes: Vec<CrashOutcome>,
+}
+
+impl StabilityOutcome {
+    /// Continuously up for the whole window
+    pub fn is_stable(&self) -> bool {
+        self.restarts == 0 && self.crashes.is_empty()
+    }
+}
+
+/// Sentinel written before a redeploy and read back after it
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct PersistenceOutcome {
+    pub protocol: String,
+    pub redeployment_id: Option<String>,
+    /// Terminal status of the redeployment
+    pub status: Option<DeploymentStatus>,
+    #[copy]
+    pub persisted: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RecoveryAction {
+    /// Same deployment, new container
+    Restart,
+    /// New deployment of the same source
+    Redeploy,
+}
+
+/// Restart or redeploy after the first deploy, with the checks run again afterwards
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct RecoveryOutcome {
+    #[copy]
+    pub action: RecoveryAction,
+    pub deployment_id: Option<String>,
+    pub status: Option<DeploymentStatus>,
+    pub healthcheck: Option<HealthcheckOutcome>,
+    pub probes: Vec<ProbeOutcome>,
+    pub error: Option<String>,
+}
+
+impl RecoveryOutcome {
+    /// Came back up and passed every check it passed on the first deploy
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none()
+            && self.status.as_ref().is_some_and(|s| s.is_success())
+            && self.healthcheck.as_ref().is_none_or(|h| h.is_healthy())
+            && self.probes.iter().all(|p| p.is_healthy())
+    }
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ServiceOutcome {
+    pub name: String,
+    /// Services deployed to several environments have an outcome for each
+    #[serde(default)]
+    pub environment_id: Option<String>,
+    pub deployment_id: Option<String>,
+    pub status: Option<DeploymentStatus>,
+    pub static_url: Option<String>,
+    pub networking: Option<Networking>,
+    pub healthcheck: Option<HealthcheckOutcome>,
+    pub probes: Vec<ProbeOutcome>,
+    pub stability: Option<StabilityOutcome>,
+    pub persistence: Option<PersistenceOutcome>,
+    pub recoveries: Vec<RecoveryOutcome>,
+    pub timeline: Option<DeploymentTimeline>,
+    pub build_logs: Vec<DeploymentLog>,
+    /// The build logs were cut off before the build finished
+    #[copy]
+    #[serde(default)]
+    pub build_logs_truncated: bool,
+    pub deploy_logs: Vec<DeploymentLog>,
+    #[copy]
+    #[serde(default)]
+    pub deploy_logs_truncated: bool,
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct TemplateOutcome {
+    code: String,
+    project_id: Option<String>,
+    started_at: DateTime<Utc>,
+    #[copy]
+    duration: Duration,
+    stages: Vec<StageOutcome>,
+    services: Vec<ServiceOutcome>,
+    smoke_tests: Vec<SmokeTestOutcome>,
+    script: Option<ScriptOutcome>,
+}
+
+impl TemplateOutcome {
+    pub fn new(code: String) -> Self {
+        Self {
+            code,
+            project_id: None,
+            started_at: Utc::now(),
+            duration: Duration::ZERO,
+            stages: Vec::new(),
+            services: Vec::new(),
+            smoke_tests: Vec::new(),
+            script: None,
+        }
+    }
+
+    pub fn set_project_id(&mut self, project_id: String) {
+        self.project_id = Some(project_id);
+    }
+
+    pub fn push_service(&mut self, service: ServiceOutcome) {
+        self.services.push(service);
+    }
+
+    pub fn push_smoke_test(&mut self, smoke_test: SmokeTestOutcome) {
+        self.smoke_tests.push(smoke_test);
+    }
+
+    pub fn set_script(&mut self, script: ScriptOutcome) {
+        self.script = Some(script);
+    }
+
+    /// Records a stage that started at `started_at` and finished now
+    pub fn record(
+        &mut self,
+        stage: StageKind,
+        started_at: DateTime<Utc>,
+        status: StageStatus,
+        error: Option<String>,
+    ) {
+        self.stages.push(StageOutcome {
+            stage,
+            status,
+            started_at,
+            duration: elapsed_since(started_at),
+            error,
+        });
+    }
+
+    /// Records a stage that failed with `err`, timeouts are kept apart from other failures
+    pub fn record_error(&mut self, stage: StageKind, started_at: DateTime<Utc>, err: &Error) {
+        self.record(
+            stage,
+            started_at,
+            StageStatus::from_error(err),
+            Some(err.to_string()),
+        );
+    }
+
+    /// Records the pipeline stopping at `stage`, failing it unless an earlier stage already
+    /// explains why, so a template that never got through its stages can't pass
+    pub fn record_stop(&mut self, stage: StageKind, started_at: DateTime<Utc>) {
+        if matches!(self.status(), StageStatus::Passed | StageStatus::Flaky) {
+            self.record(
+                stage,
+                started_at,
+                StageStatus::Failed,
+                Some("stopped the pipeline without recording a failure".to_owned()),
+            );
+        }
+    }
+
+    pub fn finish(mut self) -> Self {
+        self.duration = elapsed_since(self.started_at);
+        self
+    }
+
+    pub fn stage(&self, stage: &StageKind) -> Option<&StageOutcome> {
+        self.stages.iter().find(|s| s.stage == *stage)
+    }
+
+    /// Overall verdict: a template fails if any stage failed, and is skipped if it never deployed
+    pub fn status(&self) -> StageStatus {
+        if let Some(failure) = self.failure() {
+            failure.status
+        } else if self
+            .stage(&StageKind::Config)
+            .is_none_or(|s| s.status == StageStatus::Skipped)
+        {
+            StageStatus::Skipped
+        } else if self.stages.iter().any(|s| s.status == StageStatus::Flaky) {
+            StageStatus::Flaky
+        } else {
+            StageStatus::Passed
+        }
+    }
+
+    /// Slowest build across services, which bounds how long the template takes to build
+    pub fn build_duration(&self) -> Option<Duration> {
+        self.services
+            .iter()
+            .filter_map(|s| s.timeline.as_ref()?.build_duration())
+            .max()
+    }
+
+    /// Slowest deploy across services, from deploy start until active
+    pub fn deploy_duration(&self) -> Option<Duration> {
+        self.services
+            .iter()
+            .filter_map(|s| s.timeline.as_ref()?.deploy_duration())
+            .max()
+    }
+
+    /// The first failing stage and its error, if any
+    pub fn failure(&self) -> Option<&StageOutcome> {
+        self.stages.iter().find(|s| s.status.is_failure())
+    }
+}
+
+pub fn elapsed_since(started_at: DateTime<Utc>) -> Duration {
+    (Utc::now() - started_at).to_std().unwrap_or_default()
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct TemplateSummary {
+    code: String,
+    #[copy]
+    status: StageStatus,
+    failure: Option<String>,
+    #[copy]
+    #[serde(default)]
+    build_duration: Option<Duration>,
+    #[copy]
+    #[serde(default)]
+    deploy_duration: Option<Duration>,
+}
+
+/// Compact view of a run, persisted as `summary.json` so later runs can detect regressions
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct RunSummary {
+    started_at: DateTime<Utc>,
+    #[copy]
+    duration: Duration,
+    #[copy]
+    passed: u64,
+    #[copy]
+    flaky: u64,
+    #[copy]
+    failed: u64,
+    #[copy]
+    timed_out: u64,
+    #[copy]
+    skipped: u64,
+    templates: Vec<TemplateSummary>,
+}
+
+impl RunSummary {
+    pub fn new(started_at: DateTime<Utc>, outcomes: &[TemplateOutcome]) -> Self {
+        let templates: Vec<_> = outcomes
+            .iter()
+            .map(|o| TemplateSummary {
+                code: o.code.clone(),
+                status: o.status(),
+                failure: o.failure().map(|s| {
+                    format!(
+                        "{} {}: {}",
+                        s.stage,
+                        s.status,
+                        s.error.as_deref().unwrap_or("unknown error")
+                    )
+                }),
+                build_duration: o.build_duration(),
+                deploy_duration: o.deploy_duration(),
+            })
+            .collect();
+        let count = |status| templates.iter().filter(|t| t.status == status).count() as u64;
+
+        Self {
+            started_at,
+            duration: elapsed_since(started_at),
+            passed: count(StageStatus::Passed),
+            flaky: count(StageStatus::Flaky),
+            failed: count(StageStatus::Failed),
+            timed_out: count(StageStatus::TimedOut),
+            skipped: count(StageStatus::Skipped),
+            templates,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.templates.len() as u64
+    }
+
+    /// Templates that passed in `previous` but fail now
+    pub fn regressions(&self, previous: &RunSummary) -> Vec<TemplateSummary> {
+        self.templates
+            .iter()
+            .filter(|t| t.status.is_failure())
+            .filter(|t| {
+                previous.templates.iter().any(|p| {
+                    p.code == t.code && matches!(p.status, StageStatus::Passed | StageStatus::Flaky)
+                })
+            })
+            .cloned()
+            .collect()
+    }
+}
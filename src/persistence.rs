@@ -0,0 +1,93 @@
+use crate::{
+    outcome::PersistenceOutcome,
+    probe::{self, Probe},
+    Deployment, Result,
+};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+const SENTINEL_KEY: &str = "crater:sentinel";
+const SENTINEL_LEN: usize = 16;
+
+/// Healthy proxy of a service with a volume, which a sentinel is written through
+pub struct Sentinel {
+    /// Of the service outcome the result is recorded in
+    pub index: usize,
+    pub probe: Arc<dyn Probe>,
+    pub address: String,
+    pub variables: HashMap<String, String>,
+}
+
+pub struct Persistence;
+
+impl Persistence {
+    /// Writes a sentinel through the service's own protocol, redeploys it and reads the
+    /// sentinel back, which only works when the volume is mounted where the data lives
+    pub async fn verify(
+        token: &str,
+        deployment_id: &str,
+        probe: &dyn Probe,
+        address: &str,
+        variables: &HashMap<String, String>,
+        timeout: Duration,
+    ) -> Result<PersistenceOutcome> {
+        let deadline = Instant::now() + timeout;
+        let sentinel: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SENTINEL_LEN)
+            .collect();
+
+        let mut outcome = PersistenceOutcome {
+            protocol: probe.protocol().to_owned(),
+            redeployment_id: None,
+            status: None,
+            persisted: false,
+            error: None,
+        };
+
+        let written = probe::retry(
+            address,
+            deadline.saturating_duration_since(Instant::now()),
+            |stream| probe.write_sentinel(stream, variables, SENTINEL_KEY, &sentinel),
+        )
+        .await;
+        if let Err(err) = written {
+            warn!("Unable to write sentinel to {address}: {err}");
+            outcome.error = Some(format!("writing the sentinel failed: {err}"));
+            return Ok(outcome);
+        }
+
+        let redeployment_id = Deployment::redeploy(token, deployment_id).await?;
+        info!("Redeployed {deployment_id} as {redeployment_id}");
+        outcome.redeployment_id = Some(redeployment_id.clone());
+
+        let status = Deployment::terminal_status(token, &redeployment_id).await?;
+        outcome.status = Some(status.clone());
+        if !status.is_success() {
+            outcome.error = Some(format!("redeploy ended with {}", status.as_str()));
+            return Ok(outcome);
+        }
+
+        let read = probe::retry(
+            address,
+            deadline.saturating_duration_since(Instant::now()),
+            |stream| probe.read_sentinel(stream, variables, SENTINEL_KEY),
+        )
+        .await;
+        match read {
+            Ok(Some(value)) if value == sentinel => outcome.persisted = true,
+            Ok(Some(value)) => {
+                outcome.error = Some(format!("sentinel changed to {value} after the redeploy"))
+            }
+            Ok(None) => outcome.error = Some("sentinel is gone after the redeploy".to_owned()),
+            Err(err) => outcome.error = Some(format!("reading the sentinel failed: {err}")),
+        }
+
+        Ok(outcome)
+    }
+}
@@ -0,0 +1,500 @@
+mod build;
+mod cleanup;
+mod config;
+mod deploy;
+mod healthcheck;
+mod logs;
+mod persistence;
+mod resilience;
+mod script;
+mod smoke_tests;
+mod stability;
+mod workflow;
+
+pub use build::WaitBuilds;
+pub use cleanup::Cleanup;
+pub use config::ResolveConfig;
+pub use deploy::Deploy;
+pub use healthcheck::Healthchecks;
+pub use logs::CollectLogs;
+pub use persistence::VerifyPersistence;
+pub use resilience::RestartAndRedeploy;
+pub use script::CheckScript;
+pub use smoke_tests::RunSmokeTests;
+pub use stability::WatchStability;
+pub use workflow::WaitWorkflow;
+
+use crate::{
+    build_logs::BuildLogFollower,
+    events::{self, RunEvent},
+    metrics,
+    outcome::{ServiceOutcome, StageKind, StageStatus, TemplateOutcome},
+    persistence::Sentinel,
+    resilience::Checks,
+    Config, DeployedTemplate, Error, NewService, Result, Service, Template,
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+    future::Future,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+use tokio::{sync::broadcast, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, field, info_span, warn, Instrument, Span};
+
+/// Whether the stages after the current one still make sense
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    /// Skips every remaining stage but the ones that [`Stage::always_run`]
+    Stop,
+}
+
+/// One step of a template run, recording its own outcome in [`Context::outcome`]
+pub trait Stage: Send + Sync {
+    /// What the stage records its outcome as, [`StageKind::Custom`] for stages outside crater
+    fn kind(&self) -> StageKind;
+
+    /// Runs even after an earlier stage stopped the pipeline, like deleting the project
+    fn always_run(&self) -> bool {
+        false
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow>;
+}
+
+/// Everything stages know about the template being run, filled in as stages go
+pub struct Context {
+    /// Run directory, where logs and reports are written
+    pub dir: PathBuf,
+    pub token: String,
+    pub config: Config,
+    pub template: Template,
+    pub outcome: TemplateOutcome,
+    /// Failures of crater itself rather than of the template
+    pub errors: Vec<Box<dyn std::error::Error + Send + Sync>>,
+    /// Resolved from the template config by [`ResolveConfig`]
+    pub new_services: Vec<NewService>,
+    /// Set by [`Deploy`]
+    pub deployed: Option<DeployedTemplate>,
+    /// Whether every build succeeded, set by [`WaitBuilds`]
+    pub built: bool,
+    /// Listed with their networking by [`Healthchecks`]
+    pub services: Vec<Service>,
+    /// Added to the outcome once the pipeline is done
+    pub service_outcomes: Vec<ServiceOutcome>,
+    pub(crate) follower: Option<BuildLogFollower>,
+    /// Deploy mutation still in flight, kept running when [`Deploy`] times out or is aborted so
+    /// [`Cleanup`] can still delete the project it creates
+    pub(crate) deploying: Option<JoinHandle<Result<DeployedTemplate>>>,
+    pub(crate) sentinels: Vec<Sentinel>,
+    pub(crate) rechecks: Vec<(usize, Checks)>,
+    /// Set once the context runs in a [`Pipeline`]
+    events: Option<broadcast::Sender<RunEvent>>,
+    /// With the id [`Controls`] know this run by
+    controls: Option<(Controls, u64)>,
+    extensions: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Context {
+    pub fn new(dir: PathBuf, token: String, config: Config, template: Template) -> Self {
+        Self {
+            dir,
+            token,
+            config,
+            outcome: TemplateOutcome::new(template.code().clone()),
+            template,
+            errors: Vec::new(),
+            new_services: Vec::new(),
+            deployed: None,
+            built: false,
+            services: Vec::new(),
+            service_outcomes: Vec::new(),
+            follower: None,
+            deploying: None,
+            sentinels: Vec::new(),
+            rechecks: Vec::new(),
+            events: None,
+            controls: None,
+            extensions: HashMap::new(),
+        }
+    }
+
+    pub fn project_id(&self) -> Option<&str> {
+        self.deployed.as_ref().map(|d| d.project_id().as_str())
+    }
+
+    /// Deployment the service at `index` of [`Context::service_outcomes`] runs now, the
+    /// persistence and resilience checks replace the first one
+    pub fn latest_deployment_id(&self, index: usize) -> Option<&String> {
+        let service = self.service_outcomes.get(index)?;
+        service
+            .recoveries
+            .iter()
+            .rev()
+            .find_map(|r| r.deployment_id.as_ref())
+            .or_else(|| {
+                service
+                    .persistence
+                    .as_ref()
+                    .and_then(|p| p.redeployment_id.as_ref())
+            })
+            .or(service.deployment_id.as_ref())
+    }
+
+    /// Broadcasts to the subscribers of the pipeline running this context, if any
+    pub fn emit(&self, event: RunEvent) {
+        metrics::observe(&event);
+        if let Some(events) = &self.events {
+            // Nobody subscribing isn't an error
+            let _ = events.send(event);
+        }
+    }
+
+    /// Whether the project was asked to be kept alive for debugging instead of deleted
+    pub fn is_kept(&self) -> bool {
+        self.controls
+            .as_ref()
+            .is_some_and(|(controls, run)| controls.is_kept(*run))
+    }
+
+    /// Stores a value for later stages, one per type, returning the previous one
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.extensions
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.extensions
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        self.extensions
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Resolved variables of every listed service, by service name
+    pub async fn variables_by_service(&self) -> Result<HashMap<String, HashMap<String, String>>> {
+        let mut variables = HashMap::new();
+        let Some(project_id) = self.project_id() else {
+            return Ok(variables);
+        };
+
+        for service in &self.services {
+            for instance in service.instances() {
+                let resolved = Service::variables(
+                    &self.token,
+                    project_id,
+                    instance.environment_id(),
+                    service.id(),
+                )
+                .await?;
+                variables.insert(service.name().clone(), resolved);
+            }
+        }
+        Ok(variables)
+    }
+
+    /// Final outcome and crater errors, the build log follower is cancelled if still running
+    pub fn finish(
+        mut self,
+    ) -> (
+        TemplateOutcome,
+        Vec<Box<dyn std::error::Error + Send + Sync>>,
+    ) {
+        for service in self.service_outcomes {
+            self.outcome.push_service(service);
+        }
+        let outcome = self.outcome.finish();
+        metrics::record_template(&outcome);
+        // Outcomes carry every log line, only copied when someone listens
+        if let Some(events) = self.events.as_ref().filter(|e| e.receiver_count() > 0) {
+            let _ = events.send(RunEvent::TemplateFinished {
+                outcome: Arc::new(outcome.clone()),
+            });
+        }
+        (outcome, self.errors)
+    }
+}
+
+/// Stages run in order for every template, [`Pipeline::default`] being crater's own
+pub struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+    events: broadcast::Sender<RunEvent>,
+    controls: Controls,
+}
+
+/// Steers the templates of a running [`Pipeline`] from outside, cheap to clone
+#[derive(Clone, Default)]
+pub struct Controls {
+    state: Arc<Mutex<ControlState>>,
+}
+
+#[derive(Default)]
+struct ControlState {
+    /// Running templates by run id, a template can run more than once at a time
+    running: HashMap<u64, CancellationToken>,
+    kept: HashSet<u64>,
+    next_run: u64,
+    stopped: bool,
+}
+
+impl Controls {
+    /// Stops the run at its current stage, its project is still cleaned up. Returns whether the
+    /// run was still going, its id being the one in [`RunEvent::StageStarted`]
+    pub fn abort(&self, run: u64) -> bool {
+        let state = self.lock();
+        state.running.get(&run).map(|t| t.cancel()).is_some()
+    }
+
+    /// Aborts every running template and every template started from now on
+    pub fn stop(&self) {
+        let mut state = self.lock();
+        state.stopped = true;
+        for token in state.running.values() {
+            token.cancel();
+        }
+    }
+
+    /// Leaves the project of the run going once it's done, for debugging
+    pub fn keep(&self, run: u64) {
+        self.lock().kept.insert(run);
+    }
+
+    pub fn is_kept(&self, run: u64) -> bool {
+        self.lock().kept.contains(&run)
+    }
+
+    /// Id of the new run along with the token cancelled when it's aborted
+    fn start(&self) -> (u64, CancellationToken) {
+        let mut state = self.lock();
+        let run = state.next_run;
+        state.next_run += 1;
+        let token = CancellationToken::new();
+        if state.stopped {
+            token.cancel();
+        }
+        state.running.insert(run, token.clone());
+        (run, token)
+    }
+
+    fn finish(&self, run: u64) {
+        let mut state = self.lock();
+        state.running.remove(&run);
+        state.kept.remove(&run);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ControlState> {
+        // The state stays consistent even if a holder panicked
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        let mut pipeline = Self::new();
+        pipeline
+            .push(ResolveConfig)
+            .push(Deploy)
+            .push(WaitWorkflow)
+            .push(WaitBuilds)
+            .push(Healthchecks)
+            .push(RunSmokeTests)
+            .push(CheckScript)
+            .push(WatchStability)
+            .push(VerifyPersistence)
+            .push(RestartAndRedeploy)
+            .push(CollectLogs)
+            .push(Cleanup);
+        pipeline
+    }
+}
+
+impl Pipeline {
+    /// Pipeline without any stage
+    pub fn new() -> Self {
+        Self {
+            stages: Vec::new(),
+            events: broadcast::channel(events::CAPACITY).0,
+            controls: Controls::default(),
+        }
+    }
+
+    pub fn controls(&self) -> Controls {
+        self.controls.clone()
+    }
+
+    /// Receives every event from now on, across all the templates the pipeline runs
+    pub fn subscribe(&self) -> broadcast::Receiver<RunEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn emit(&self, event: RunEvent) {
+        // Nobody subscribing isn't an error
+        let _ = self.events.send(event);
+    }
+
+    pub fn push(&mut self, stage: impl Stage + 'static) -> &mut Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Inserts `stage` right before the stage of kind `before`, at the end if there's none
+    pub fn insert_before(&mut self, before: &StageKind, stage: impl Stage + 'static) -> &mut Self {
+        let index = self.position(before).unwrap_or(self.stages.len());
+        self.stages.insert(index, Box::new(stage));
+        self
+    }
+
+    /// Inserts `stage` right after the stage of kind `after`, at the end if there's none
+    pub fn insert_after(&mut self, after: &StageKind, stage: impl Stage + 'static) -> &mut Self {
+        let index = self
+            .position(after)
+            .map_or(self.stages.len(), |index| index + 1);
+        self.stages.insert(index, Box::new(stage));
+        self
+    }
+
+    pub fn remove(&mut self, kind: &StageKind) -> &mut Self {
+        self.stages.retain(|stage| stage.kind() != *kind);
+        self
+    }
+
+    pub fn kinds(&self) -> Vec<StageKind> {
+        self.stages.iter().map(|stage| stage.kind()).collect()
+    }
+
+    pub async fn run(&self, context: &mut Context) {
+        // Each template is its own trace, linked to the run it's part of
+        let span = info_span!(
+            parent: None,
+            "template",
+            template.code = %context.template.code(),
+            project.id = field::Empty,
+        );
+        span.follows_from(Span::current());
+        self.run_stages(context).instrument(span.clone()).await;
+        if let Some(project_id) = context.outcome.project_id() {
+            span.record("project.id", project_id.as_str());
+        }
+    }
+
+    async fn run_stages(&self, context: &mut Context) {
+        context.events = Some(self.events.clone());
+        let (run, aborted) = self.controls.start();
+        context.controls = Some((self.controls.clone(), run));
+        let code = context.template.code().clone();
+
+        let mut stopped = false;
+        for stage in &self.stages {
+            if (stopped || aborted.is_cancelled()) && !stage.always_run() {
+                continue;
+            }
+
+            debug!("Running {} for {code}", stage.kind());
+            context.emit(RunEvent::StageStarted {
+                template: code.clone(),
+                run,
+                stage: stage.kind(),
+            });
+            let span = info_span!(
+                "stage",
+                stage = %stage.kind(),
+                otel.status_code = field::Empty,
+            );
+            let started_at = Utc::now();
+            // Stages that always run are the ones cleaning up after an abort
+            let flow = if stage.always_run() {
+                stage.run(context).instrument(span.clone()).await
+            } else {
+                tokio::select! {
+                    flow = stage.run(context).instrument(span.clone()) => flow,
+                    () = aborted.cancelled() => {
+                        warn!("Aborted {code} during {}", stage.kind());
+                        context.outcome.record(
+                            stage.kind(),
+                            started_at,
+                            StageStatus::Failed,
+                            Some("aborted".to_owned()),
+                        );
+                        Flow::Stop
+                    }
+                }
+            };
+            if flow == Flow::Stop {
+                context.outcome.record_stop(stage.kind(), started_at);
+                stopped = true;
+            }
+            if context
+                .outcome
+                .stage(&stage.kind())
+                .is_some_and(|s| s.status().is_failure())
+            {
+                span.record("otel.status_code", "ERROR");
+            }
+        }
+        self.controls.finish(run);
+    }
+
+    fn position(&self, kind: &StageKind) -> Option<usize> {
+        self.stages.iter().position(|stage| stage.kind() == *kind)
+    }
+}
+
+/// Fails with [`Error::Timeout`] if `future` doesn't finish within `timeout`
+pub(crate) async fn timeout<T>(
+    stage: StageKind,
+    timeout: Duration,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let started = Instant::now();
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| Error::Timeout {
+            stage,
+            elapsed: started.elapsed(),
+        })?
+}
+
+/// Turns a Railway wait running out of time into a timeout of `stage`
+pub(crate) fn stage_error(stage: StageKind, err: Error) -> Error {
+    match err {
+        Error::RailwayTimeout(elapsed) => Error::Timeout { stage, elapsed },
+        err => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_of_the_same_template_are_steered_apart() {
+        let controls = Controls::default();
+        let (first, first_aborted) = controls.start();
+        let (second, second_aborted) = controls.start();
+        assert_ne!(first, second);
+
+        controls.keep(first);
+        assert!(controls.abort(first));
+        assert!(first_aborted.is_cancelled());
+        assert!(!second_aborted.is_cancelled());
+        assert!(!controls.is_kept(second));
+
+        controls.keep(second);
+        controls.finish(first);
+        assert!(!controls.abort(first));
+        assert!(controls.is_kept(second));
+        assert!(controls.abort(second));
+    }
+}
@@ -0,0 +1,84 @@
+use super::{stage_error, Context, Flow, Stage};
+use crate::{
+    outcome::{StageKind, StageStatus},
+    RunEvent, Service,
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Waits for every service to build and deploy, failed builds still let logs be collected
+pub struct WaitBuilds;
+
+impl Stage for WaitBuilds {
+    fn kind(&self) -> StageKind {
+        StageKind::Build
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            let Some(deployed) = &context.deployed else {
+                return Flow::Stop;
+            };
+
+            info!("Waiting for all builds: {}", context.template.code());
+            let started_at = Utc::now();
+            let emitter = &*context;
+            let builds = match Service::wait_for_all_builds(
+                &context.token,
+                deployed.project_id(),
+                Duration::from_secs(context.config.timeouts.build),
+                |build| {
+                    emitter.emit(RunEvent::BuildStatusChanged {
+                        template: emitter.template.code().clone(),
+                        service: build.service_name().clone(),
+                        deployment_id: build.deployment_id().clone(),
+                        status: build.status().clone(),
+                    })
+                },
+            )
+            .await
+            .map_err(|err| stage_error(StageKind::Build, err))
+            {
+                Ok(builds) => builds,
+                Err(err) => {
+                    error!(
+                        "Unable to wait for all builds for {}: {err}",
+                        context.template.code()
+                    );
+                    context
+                        .outcome
+                        .record_error(StageKind::Build, started_at, &err);
+                    context.errors.push(Box::new(err));
+                    return Flow::Stop;
+                }
+            };
+
+            let failed_builds: Vec<_> = builds
+                .iter()
+                .filter(|b| !b.status().is_success())
+                .map(|b| format!("{} is {}", b.service_name(), b.status()))
+                .collect();
+            context.built = failed_builds.is_empty();
+            if context.built {
+                context
+                    .outcome
+                    .record(StageKind::Build, started_at, StageStatus::Passed, None);
+            } else {
+                error!(
+                    "Deployments failed for {}: {}",
+                    context.template.code(),
+                    failed_builds.join(", ")
+                );
+                context.outcome.record(
+                    StageKind::Build,
+                    started_at,
+                    StageStatus::Failed,
+                    Some(failed_builds.join(", ")),
+                );
+            }
+            Flow::Continue
+        })
+    }
+}
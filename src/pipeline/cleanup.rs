@@ -0,0 +1,88 @@
+use super::{Context, Flow, Stage};
+use crate::{
+    outcome::{StageKind, StageStatus},
+    Project, RunEvent,
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Deletes the deployed project, even when an earlier stage stopped the pipeline
+pub struct Cleanup;
+
+impl Stage for Cleanup {
+    fn kind(&self) -> StageKind {
+        StageKind::Cleanup
+    }
+
+    fn always_run(&self) -> bool {
+        true
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            // The deploy stage gave up on the mutation, the project it creates still gets deleted
+            if let Some(deploying) = context.deploying.take() {
+                let deadline = Duration::from_secs(context.config.timeouts.deploy);
+                if let Ok(Ok(Ok(deployed))) = tokio::time::timeout(deadline, deploying).await {
+                    warn!(
+                        "Deploy of {} finished late as project {}",
+                        context.template.code(),
+                        deployed.project_id()
+                    );
+                    context
+                        .outcome
+                        .set_project_id(deployed.project_id().clone());
+                    context.emit(RunEvent::Deployed {
+                        template: context.template.code().clone(),
+                        project_id: deployed.project_id().clone(),
+                    });
+                    context.deployed = Some(deployed);
+                }
+            }
+
+            // Nothing was deployed when the config was skipped or the deploy failed
+            let Some(deployed) = &context.deployed else {
+                return Flow::Continue;
+            };
+
+            let started_at = Utc::now();
+            if context.is_kept() {
+                warn!(
+                    "Keeping project {} of {} alive",
+                    deployed.project_id(),
+                    context.template.code()
+                );
+                context.outcome.record(
+                    StageKind::Cleanup,
+                    started_at,
+                    StageStatus::Skipped,
+                    Some(format!("project {} kept alive", deployed.project_id())),
+                );
+                return Flow::Continue;
+            }
+
+            let result = Project::delete(&context.token, deployed.project_id()).await;
+            context.emit(RunEvent::Cleaned {
+                template: context.template.code().clone(),
+                project_id: deployed.project_id().clone(),
+                error: result.as_ref().err().map(|err| err.to_string()),
+            });
+            if let Err(err) = result {
+                error!("Unable to delete project {}: {err}", deployed.project_id());
+                context
+                    .outcome
+                    .record_error(StageKind::Cleanup, started_at, &err);
+                context.errors.push(Box::new(err));
+            } else {
+                context
+                    .outcome
+                    .record(StageKind::Cleanup, started_at, StageStatus::Passed, None);
+            }
+
+            info!("Processed template: {}", context.template.code());
+            Flow::Continue
+        })
+    }
+}
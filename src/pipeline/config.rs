@@ -0,0 +1,138 @@
+use super::{Context, Flow, Stage};
+use crate::{
+    environment::{DeserializedEnvironment, DeserializedServiceSource},
+    outcome::{StageKind, StageStatus},
+    Error, NewService, NewVolume, Result, Template,
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use serde::Deserialize;
+use std::collections::HashMap;
+use tracing::{error, warn};
+
+/// Resolves the services to deploy from the template config, skipping templates that can't be
+pub struct ResolveConfig;
+
+impl Stage for ResolveConfig {
+    fn kind(&self) -> StageKind {
+        StageKind::Config
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            let started_at = Utc::now();
+            match new_services(&context.template) {
+                Ok(Some(services)) => {
+                    context.new_services = services;
+                    context.outcome.record(
+                        StageKind::Config,
+                        started_at,
+                        StageStatus::Passed,
+                        None,
+                    );
+                    Flow::Continue
+                }
+                Ok(None) => {
+                    context.outcome.record(
+                        StageKind::Config,
+                        started_at,
+                        StageStatus::Skipped,
+                        None,
+                    );
+                    Flow::Stop
+                }
+                Err(err) => {
+                    error!(
+                        "Unable to resolve services for template {}: {err}",
+                        context.template.code()
+                    );
+                    context
+                        .outcome
+                        .record_error(StageKind::Config, started_at, &err);
+                    context.errors.push(Box::new(err));
+                    Flow::Stop
+                }
+            }
+        })
+    }
+}
+
+/// Resolves the services to deploy from the template config, `None` means the template is skipped
+fn new_services(template: &Template) -> Result<Option<Vec<NewService>>> {
+    if template.serialized_config().is_null() {
+        warn!("No serialized config for {}, skipping it", template.code());
+        return Ok(None);
+    }
+
+    let config = Option::<DeserializedEnvironment>::deserialize(template.serialized_config())?;
+
+    let mut services = Vec::new();
+
+    for (id, service) in config.as_ref().map_or(&HashMap::new(), |c| c.services()) {
+        let mut variables = HashMap::new();
+        for (name, variable) in service.variables() {
+            variables.insert("RAILWAY_BETA_ENABLE_BUILD_V2".to_owned(), "1".to_owned());
+
+            if let Some(value) = variable.default_value().clone().filter(|v| !v.is_empty()) {
+                variables.insert(name.clone(), value);
+            } else if !variable.is_optional().unwrap_or_default() {
+                warn!("Missing env var {name} for template {}", template.code());
+                return Ok(None);
+            }
+        }
+
+        let volumes = service
+            .volume_mounts()
+            .values()
+            .map(|volume| NewVolume {
+                mount_path: volume.mount_path().clone(),
+            })
+            .collect();
+
+        services.push(NewService {
+            id: id.clone(),
+            has_domain: service
+                .networking()
+                .as_ref()
+                .map(|n| !n.service_domains().is_empty()),
+            healthcheck_path: service
+                .deploy()
+                .as_ref()
+                .and_then(|d| d.healthcheck_path().clone()),
+            name: service.name().clone(),
+            root_directory: match service.source() {
+                Some(DeserializedServiceSource::Image { .. }) => None,
+                Some(DeserializedServiceSource::Repo { root_directory, .. }) => {
+                    root_directory.clone()
+                }
+                None => None,
+            },
+            service_icon: service.icon().clone(),
+            service_name: service.name().clone(),
+            start_command: service
+                .deploy()
+                .as_ref()
+                .and_then(|d| d.start_command().clone()),
+            tcp_proxy_application_port: service
+                .networking()
+                .as_ref()
+                .and_then(|n| {
+                    n.tcp_proxies()
+                        .keys()
+                        .next()
+                        .map(|k| k.parse::<i64>().map_err(|err| (err, k.clone())))
+                })
+                .transpose()
+                .map_err(|(err, port)| Error::ParseIntWithMetadata(err, port))?,
+            template: match service.source() {
+                Some(DeserializedServiceSource::Image { image }) => image.clone(),
+                Some(DeserializedServiceSource::Repo { repo, .. }) => repo.clone(),
+                None => service.name().clone(),
+            },
+            variables,
+            volumes,
+        });
+    }
+
+    Ok(Some(services))
+}
@@ -0,0 +1,84 @@
+use super::{timeout, Context, Flow, Stage};
+use crate::{
+    build_logs::BuildLogFollower,
+    outcome::{StageKind, StageStatus},
+    Error, RunEvent, Template,
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Deploys the resolved services into a new project, and starts following their build logs
+pub struct Deploy;
+
+impl Stage for Deploy {
+    fn kind(&self) -> StageKind {
+        StageKind::Deploy
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            info!("Deploying {}", context.template.code());
+            context.emit(RunEvent::Deploying {
+                template: context.template.code().clone(),
+            });
+            let started_at = Utc::now();
+            // Spawned so a timeout or an abort can't drop the mutation after Railway created the
+            // project but before its id came back
+            let token = context.token.clone();
+            let services = context.new_services.clone();
+            let code = context.template.code().clone();
+            let deploying = context.deploying.insert(tokio::spawn(async move {
+                Template::deploy(&token, services, &code).await
+            }));
+            let result = timeout(
+                StageKind::Deploy,
+                Duration::from_secs(context.config.timeouts.deploy),
+                async {
+                    deploying
+                        .await
+                        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+                },
+            )
+            .await;
+            if !matches!(result, Err(Error::Timeout { .. })) {
+                context.deploying = None;
+            }
+            let deployed = match result {
+                Ok(deployed) => deployed,
+                Err(err) => {
+                    error!(
+                        "Unable to deploy template {}: {err}",
+                        context.template.code()
+                    );
+                    context
+                        .outcome
+                        .record_error(StageKind::Deploy, started_at, &err);
+                    context.errors.push(Box::new(err));
+                    return Flow::Stop;
+                }
+            };
+            context
+                .outcome
+                .record(StageKind::Deploy, started_at, StageStatus::Passed, None);
+            context
+                .outcome
+                .set_project_id(deployed.project_id().clone());
+            context.emit(RunEvent::Deployed {
+                template: context.template.code().clone(),
+                project_id: deployed.project_id().clone(),
+            });
+
+            // Stopping the pipeline early drops the follower, whatever was streamed is kept
+            context.follower = Some(BuildLogFollower::start(
+                &context.token,
+                deployed.project_id(),
+                &context.dir,
+                context.template.code(),
+            ));
+            context.deployed = Some(deployed);
+            Flow::Continue
+        })
+    }
+}
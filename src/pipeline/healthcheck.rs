@@ -0,0 +1,240 @@
+use super::{Context, Flow, Stage};
+use crate::{
+    healthcheck::Healthcheck,
+    outcome::{elapsed_since, ServiceOutcome, StageKind, StageStatus},
+    persistence::Sentinel,
+    probe::Probes,
+    resilience::Checks,
+    Error, RunEvent, Service,
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+
+/// Used when the service doesn't configure its own healthcheck timeout, in seconds
+const DEFAULT_HEALTHCHECK_TIMEOUT: u64 = 300;
+
+/// Lists the deployed services, then checks their HTTP healthcheck paths and probes their
+/// TCP proxies
+pub struct Healthchecks;
+
+impl Stage for Healthchecks {
+    fn kind(&self) -> StageKind {
+        StageKind::Healthcheck
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            let Some(deployed) = &context.deployed else {
+                return Flow::Stop;
+            };
+            let token = context.token.as_str();
+            let code = context.template.code();
+            let built = context.built;
+
+            info!("Listing services");
+            let started_at = Utc::now();
+            context.services =
+                match Service::list_with_networking(token, deployed.project_id()).await {
+                    Ok(services) => services,
+                    Err(err) => {
+                        error!("Unable to list services for {code}: {err}");
+                        context
+                            .outcome
+                            .record_error(StageKind::Healthcheck, started_at, &err);
+                        context.errors.push(Box::new(err));
+                        return Flow::Stop;
+                    }
+                };
+
+            info!("Running healthchecks for {code}");
+            let deadline =
+                Instant::now() + Duration::from_secs(context.config.timeouts.healthcheck);
+            let probe_registry = Probes::default();
+            // Only services the template gives a volume are expected to keep their data
+            let with_volumes: Vec<_> = context
+                .new_services
+                .iter()
+                .filter(|s| !s.volumes.is_empty())
+                .map(|s| &s.service_name)
+                .collect();
+            for service in &context.services {
+                for instance in service.instances() {
+                    // Failed builds are still listed so their logs get collected
+                    let healthcheck_target =
+                        match (instance.static_url(), instance.healthcheck_path()) {
+                            (Some(static_url), Some(path)) if built => {
+                                let url = format!(
+                                    "https://{static_url}/{}",
+                                    path.trim_start_matches('/')
+                                );
+                                let timeout = Duration::from_secs(
+                                    instance
+                                        .healthcheck_timeout()
+                                        .unwrap_or(DEFAULT_HEALTHCHECK_TIMEOUT),
+                                );
+                                Some((url, timeout))
+                            }
+                            _ => None,
+                        };
+                    let healthcheck = match &healthcheck_target {
+                        Some((url, timeout)) => {
+                            let timeout =
+                                (*timeout).min(deadline.saturating_duration_since(Instant::now()));
+                            Some(Healthcheck::check(url.clone(), timeout).await)
+                        }
+                        None => None,
+                    };
+
+                    // Databases and other TCP services have no HTTP healthcheck, their proxies
+                    // are probed
+                    let probe = probe_registry.select(
+                        &context.config.probes,
+                        code,
+                        service.name(),
+                        instance.source_image().as_deref(),
+                    );
+                    let mut probes = Vec::new();
+                    let mut variables = HashMap::new();
+                    if built && !instance.networking().tcp_proxies().is_empty() {
+                        variables = Service::variables(
+                            token,
+                            deployed.project_id(),
+                            instance.environment_id(),
+                            service.id(),
+                        )
+                        .await
+                        .unwrap_or_else(|err| {
+                            warn!("Unable to fetch variables of {}: {err}", service.name());
+                            HashMap::new()
+                        });
+
+                        for proxy in instance.networking().tcp_proxies() {
+                            let timeout = deadline.saturating_duration_since(Instant::now());
+                            probes.push(
+                                Probes::check(probe.as_ref(), proxy.address(), &variables, timeout)
+                                    .await,
+                            );
+                        }
+
+                        let healthy = probes.iter().find(|p| p.is_healthy());
+                        if let Some(healthy) = healthy.filter(|_| {
+                            probe.supports_sentinel() && with_volumes.contains(&service.name())
+                        }) {
+                            context.sentinels.push(Sentinel {
+                                index: context.service_outcomes.len(),
+                                probe: probe.clone(),
+                                address: healthy.address.clone(),
+                                variables: variables.clone(),
+                            });
+                        }
+                    }
+
+                    if built {
+                        let checks = Checks {
+                            service_id: service.id().clone(),
+                            environment_id: instance.environment_id().clone(),
+                            healthcheck: healthcheck_target,
+                            probe,
+                            addresses: probes.iter().map(|p| p.address.clone()).collect(),
+                            variables,
+                        };
+                        context
+                            .rechecks
+                            .push((context.service_outcomes.len(), checks));
+                    }
+
+                    let results = healthcheck
+                        .iter()
+                        .map(|h| (h.url.clone(), h.is_healthy(), h.attempts))
+                        .chain(
+                            probes
+                                .iter()
+                                .map(|p| (p.target(), p.is_healthy(), p.attempts)),
+                        );
+                    for (target, healthy, attempts) in results {
+                        context.emit(RunEvent::HealthcheckResult {
+                            template: code.clone(),
+                            service: service.name().clone(),
+                            target,
+                            healthy,
+                            attempts,
+                        });
+                    }
+
+                    context.service_outcomes.push(ServiceOutcome {
+                        name: service.name().clone(),
+                        environment_id: Some(instance.environment_id().clone()),
+                        deployment_id: instance.deployment_id().clone(),
+                        status: instance.status().clone(),
+                        static_url: instance.static_url().clone(),
+                        networking: Some(instance.networking().clone()),
+                        healthcheck,
+                        probes,
+                        ..ServiceOutcome::default()
+                    });
+                }
+            }
+
+            // (target, healthy, attempts, reason) of every HTTP healthcheck and probe
+            let checks: Vec<_> = context
+                .service_outcomes
+                .iter()
+                .flat_map(|s| {
+                    s.healthcheck
+                        .iter()
+                        .map(|h| {
+                            let reason = h
+                                .status_code
+                                .map(|code| format!("status {code}"))
+                                .or_else(|| h.error.clone())
+                                .unwrap_or_default();
+                            (h.url.clone(), h.is_healthy(), h.attempts, reason)
+                        })
+                        .chain(s.probes.iter().map(|p| {
+                            let reason = p.error.clone().unwrap_or_default();
+                            (p.target(), p.is_healthy(), p.attempts, reason)
+                        }))
+                })
+                .collect();
+            let outcome = &mut context.outcome;
+            if checks.is_empty() {
+                outcome.record(
+                    StageKind::Healthcheck,
+                    started_at,
+                    StageStatus::Skipped,
+                    None,
+                );
+            } else if checks.iter().any(|(_, healthy, ..)| !healthy) && Instant::now() >= deadline {
+                let err = Error::Timeout {
+                    stage: StageKind::Healthcheck,
+                    elapsed: elapsed_since(started_at),
+                };
+                outcome.record_error(StageKind::Healthcheck, started_at, &err);
+            } else if let Some((target, _, _, reason)) =
+                checks.iter().find(|(_, healthy, ..)| !healthy)
+            {
+                outcome.record(
+                    StageKind::Healthcheck,
+                    started_at,
+                    StageStatus::Failed,
+                    Some(format!("{target} is unhealthy: {reason}")),
+                );
+            } else if checks.iter().any(|(_, _, attempts, _)| *attempts > 1) {
+                outcome.record(StageKind::Healthcheck, started_at, StageStatus::Flaky, None);
+            } else {
+                outcome.record(
+                    StageKind::Healthcheck,
+                    started_at,
+                    StageStatus::Passed,
+                    None,
+                );
+            }
+            Flow::Continue
+        })
+    }
+}
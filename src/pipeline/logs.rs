@@ -0,0 +1,130 @@
+use super::{timeout, Context, Flow, Stage};
+use crate::{
+    build_logs,
+    outcome::{StageKind, StageStatus},
+    Deployment,
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tracing::error;
+
+/// Collects the build and deploy logs and the timeline of every listed deployment
+pub struct CollectLogs;
+
+impl Stage for CollectLogs {
+    fn kind(&self) -> StageKind {
+        StageKind::Logs
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            let started_at = Utc::now();
+            let deadline = Instant::now() + Duration::from_secs(context.config.timeouts.logs);
+            let streamed = match context.follower.take() {
+                Some(follower) => follower.stop().await,
+                None => HashMap::new(),
+            };
+            let token = context.token.as_str();
+            let code = context.template.code();
+            let mut logs_failure: Option<(StageStatus, String)> = None;
+            // The persistence and resilience checks may have redeployed since the build
+            let deployment_ids: Vec<_> = (0..context.service_outcomes.len())
+                .map(|i| context.latest_deployment_id(i).cloned())
+                .collect();
+            for (service, deployment_id) in context.service_outcomes.iter_mut().zip(deployment_ids)
+            {
+                let Some(deployment_id) = deployment_id else {
+                    continue;
+                };
+
+                let logs = match streamed.get(&deployment_id) {
+                    Some(path) => build_logs::read(path).await,
+                    // Deployments created after the build stage were never followed
+                    None => {
+                        let path = build_logs::log_path(
+                            &context.dir,
+                            code,
+                            &service.name,
+                            service.environment_id.as_deref().unwrap_or_default(),
+                        );
+                        match Deployment::follow_build_logs(
+                            token,
+                            &deployment_id,
+                            deadline.saturating_duration_since(Instant::now()),
+                        )
+                        .await
+                        {
+                            Ok(followed) => {
+                                service.build_logs_truncated = followed.truncated;
+                                build_logs::write(&path, &followed.logs)
+                                    .await
+                                    .map(|()| followed.logs)
+                            }
+                            Err(err) => Err(err),
+                        }
+                    }
+                };
+                match logs {
+                    Ok(logs) => service.build_logs = logs,
+                    Err(err) => {
+                        error!("Unable to fetch build logs: {err}");
+                        logs_failure = Some((StageStatus::from_error(&err), err.to_string()));
+                        context.errors.push(Box::new(err));
+                        continue;
+                    }
+                }
+
+                match Deployment::follow_logs(
+                    token,
+                    &deployment_id,
+                    deadline.saturating_duration_since(Instant::now()),
+                )
+                .await
+                {
+                    Ok(followed) => {
+                        service.deploy_logs = followed.logs;
+                        service.deploy_logs_truncated = followed.truncated;
+                    }
+                    Err(err) => {
+                        error!("Unable to fetch deploy logs: {err}");
+                        logs_failure = Some((StageStatus::from_error(&err), err.to_string()));
+                        context.errors.push(Box::new(err));
+                    }
+                }
+
+                match timeout(
+                    StageKind::Logs,
+                    deadline.saturating_duration_since(Instant::now()),
+                    Deployment::timeline(token, &deployment_id),
+                )
+                .await
+                {
+                    Ok(timeline) => service.timeline = Some(timeline),
+                    Err(err) => {
+                        error!("Unable to fetch deployment timeline: {err}");
+                        logs_failure = Some((StageStatus::from_error(&err), err.to_string()));
+                        context.errors.push(Box::new(err));
+                    }
+                }
+            }
+
+            match logs_failure {
+                Some((status, err)) => {
+                    context
+                        .outcome
+                        .record(StageKind::Logs, started_at, status, Some(err))
+                }
+                None => {
+                    context
+                        .outcome
+                        .record(StageKind::Logs, started_at, StageStatus::Passed, None)
+                }
+            }
+            Flow::Continue
+        })
+    }
+}
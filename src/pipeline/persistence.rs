@@ -0,0 +1,110 @@
+use super::{timeout, Context, Flow, Stage};
+use crate::{
+    outcome::{StageKind, StageStatus},
+    persistence::Persistence,
+};
+use chrono::Utc;
+use futures_util::future::{join_all, BoxFuture};
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Writes a sentinel to every service with a volume, redeploys it and reads the sentinel back
+pub struct VerifyPersistence;
+
+impl Stage for VerifyPersistence {
+    fn kind(&self) -> StageKind {
+        StageKind::Persistence
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            let code = context.template.code().clone();
+            let started_at = Utc::now();
+            let sentinels: Vec<_> = std::mem::take(&mut context.sentinels)
+                .into_iter()
+                .filter_map(|sentinel| {
+                    let deployment_id = context.service_outcomes[sentinel.index]
+                        .deployment_id
+                        .clone()?;
+                    Some((deployment_id, sentinel))
+                })
+                .collect();
+            if sentinels.is_empty() {
+                context.outcome.record(
+                    StageKind::Persistence,
+                    started_at,
+                    StageStatus::Skipped,
+                    None,
+                );
+                return Flow::Continue;
+            }
+
+            info!(
+                "Checking volume persistence of {} services of {code}",
+                sentinels.len()
+            );
+            let token = context.token.as_str();
+            let persistence_timeout = Duration::from_secs(context.config.timeouts.persistence);
+            let results = join_all(sentinels.iter().map(|(deployment_id, sentinel)| {
+                timeout(
+                    StageKind::Persistence,
+                    persistence_timeout,
+                    Persistence::verify(
+                        token,
+                        deployment_id,
+                        sentinel.probe.as_ref(),
+                        &sentinel.address,
+                        &sentinel.variables,
+                        persistence_timeout,
+                    ),
+                )
+            }))
+            .await;
+
+            let mut lost = Vec::new();
+            let mut persistence_error = None;
+            for ((_, sentinel), result) in sentinels.iter().zip(results) {
+                let service = &mut context.service_outcomes[sentinel.index];
+                match result {
+                    Ok(persistence) => {
+                        if !persistence.persisted {
+                            lost.push(format!(
+                                "{}: {}",
+                                service.name,
+                                persistence.error.as_deref().unwrap_or_default()
+                            ));
+                        }
+                        service.persistence = Some(persistence);
+                    }
+                    Err(err) => {
+                        error!("Unable to check persistence of {}: {err}", service.name);
+                        persistence_error = Some(err);
+                    }
+                }
+            }
+
+            if let Some(err) = persistence_error {
+                context
+                    .outcome
+                    .record_error(StageKind::Persistence, started_at, &err);
+                context.errors.push(Box::new(err));
+            } else if !lost.is_empty() {
+                error!("Data lost across redeploys in {code}: {}", lost.join(", "));
+                context.outcome.record(
+                    StageKind::Persistence,
+                    started_at,
+                    StageStatus::Failed,
+                    Some(lost.join(", ")),
+                );
+            } else {
+                context.outcome.record(
+                    StageKind::Persistence,
+                    started_at,
+                    StageStatus::Passed,
+                    None,
+                );
+            }
+            Flow::Continue
+        })
+    }
+}
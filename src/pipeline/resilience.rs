@@ -0,0 +1,135 @@
+use super::{stage_error, timeout, Context, Flow, Stage};
+use crate::{
+    outcome::{StageKind, StageStatus},
+    resilience::Resilience,
+};
+use chrono::Utc;
+use futures_util::future::{join_all, BoxFuture};
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Restarts then redeploys every healthy service and checks it again, when
+/// [`crate::Config::resilience`] is enabled
+pub struct RestartAndRedeploy;
+
+impl Stage for RestartAndRedeploy {
+    fn kind(&self) -> StageKind {
+        StageKind::Resilience
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            let code = context.template.code().clone();
+            let started_at = Utc::now();
+            // The persistence check may already have replaced the first deployment
+            let rechecks: Vec<_> = std::mem::take(&mut context.rechecks)
+                .into_iter()
+                .filter(|(i, _)| {
+                    context.service_outcomes[*i]
+                        .status
+                        .as_ref()
+                        .is_some_and(|s| s.is_success())
+                })
+                .filter_map(|(i, checks)| {
+                    let deployment_id = context.latest_deployment_id(i)?.clone();
+                    Some((i, deployment_id, checks))
+                })
+                .collect();
+            if !context.config.resilience || rechecks.is_empty() {
+                context.outcome.record(
+                    StageKind::Resilience,
+                    started_at,
+                    StageStatus::Skipped,
+                    None,
+                );
+                return Flow::Continue;
+            }
+
+            info!(
+                "Restarting and redeploying {} services of {code}",
+                rechecks.len()
+            );
+            let token = context.token.as_str();
+            let resilience_timeout = Duration::from_secs(context.config.timeouts.resilience);
+            let results = join_all(
+                rechecks
+                    .iter()
+                    .map(|(_, deployment_id, checks)| async move {
+                        timeout(
+                            StageKind::Resilience,
+                            resilience_timeout,
+                            Resilience::verify(token, deployment_id, checks, resilience_timeout),
+                        )
+                        .await
+                        .map_err(|err| stage_error(StageKind::Resilience, err))
+                    }),
+            )
+            .await;
+
+            let mut broken = Vec::new();
+            let mut resilience_error = None;
+            for ((i, ..), result) in rechecks.iter().zip(results) {
+                let service = &mut context.service_outcomes[*i];
+                match result {
+                    Ok(recoveries) => {
+                        for recovery in recoveries.iter().filter(|r| !r.is_healthy()) {
+                            let reason = recovery
+                                .error
+                                .clone()
+                                .or_else(|| {
+                                    recovery
+                                        .healthcheck
+                                        .as_ref()
+                                        .filter(|h| !h.is_healthy())
+                                        .map(|h| format!("{} is unhealthy", h.url))
+                                })
+                                .or_else(|| {
+                                    recovery
+                                        .probes
+                                        .iter()
+                                        .find(|p| !p.is_healthy())
+                                        .map(|p| format!("{} is unhealthy", p.target()))
+                                })
+                                .unwrap_or_default();
+                            broken.push(format!(
+                                "{} after {}: {reason}",
+                                service.name, recovery.action
+                            ));
+                        }
+                        service.recoveries = recoveries;
+                    }
+                    Err(err) => {
+                        error!("Unable to restart and redeploy {}: {err}", service.name);
+                        resilience_error = Some(err);
+                    }
+                }
+            }
+
+            if let Some(err) = resilience_error {
+                context
+                    .outcome
+                    .record_error(StageKind::Resilience, started_at, &err);
+                context.errors.push(Box::new(err));
+            } else if !broken.is_empty() {
+                error!(
+                    "Services of {code} broke after a restart or redeploy: {}",
+                    broken.join(", ")
+                );
+                context.outcome.record(
+                    StageKind::Resilience,
+                    started_at,
+                    StageStatus::Failed,
+                    Some(broken.join(", ")),
+                );
+            } else {
+                context.outcome.record(
+                    StageKind::Resilience,
+                    started_at,
+                    StageStatus::Passed,
+                    None,
+                );
+            }
+            Flow::Continue
+        })
+    }
+}
@@ -0,0 +1,126 @@
+use super::{timeout, Context, Flow, Stage};
+use crate::{
+    outcome::{StageKind, StageStatus},
+    script::{Script, ScriptContext, ScriptOutput, ScriptService},
+    Error,
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::time::Duration;
+use tracing::error;
+
+/// Runs the check script configured for the template with the project in its environment
+pub struct CheckScript;
+
+impl Stage for CheckScript {
+    fn kind(&self) -> StageKind {
+        StageKind::Script
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            let code = context.template.code().clone();
+            let started_at = Utc::now();
+            let script = context
+                .config
+                .scripts
+                .iter()
+                .find(|s| s.template == code)
+                .cloned();
+            let project_id = context.project_id().map(str::to_owned);
+            let (script, project_id) = match (script, project_id) {
+                (Some(script), Some(project_id)) if context.built => (script, project_id),
+                _ => {
+                    context.outcome.record(
+                        StageKind::Script,
+                        started_at,
+                        StageStatus::Skipped,
+                        None,
+                    );
+                    return Flow::Continue;
+                }
+            };
+
+            let output = ScriptOutput::default();
+            let run_script = async {
+                let mut variables = context.variables_by_service().await?;
+                let script_context = ScriptContext {
+                    template: code.clone(),
+                    project_id,
+                    services: context
+                        .services
+                        .iter()
+                        .flat_map(|s| s.instances().iter().map(move |i| (s.name(), i)))
+                        .map(|(name, instance)| ScriptService {
+                            name: name.clone(),
+                            url: instance
+                                .static_url()
+                                .as_ref()
+                                .map(|url| format!("https://{url}")),
+                            tcp_proxies: instance
+                                .networking()
+                                .tcp_proxies()
+                                .iter()
+                                .map(|p| p.address())
+                                .collect(),
+                            variables: variables.remove(name).unwrap_or_default(),
+                        })
+                        .collect(),
+                };
+                Script::run(&script, &script_context, &output).await
+            };
+
+            match timeout(
+                StageKind::Script,
+                Duration::from_secs(context.config.timeouts.script),
+                run_script,
+            )
+            .await
+            {
+                Ok(script_outcome) => {
+                    if script_outcome.is_passed() {
+                        context.outcome.record(
+                            StageKind::Script,
+                            started_at,
+                            StageStatus::Passed,
+                            None,
+                        );
+                    } else {
+                        let reason = match script_outcome.exit_code {
+                            Some(code) => format!("exited with {code}"),
+                            None => "killed by a signal".to_owned(),
+                        };
+                        let last_line = script_outcome
+                            .stderr
+                            .lines()
+                            .rev()
+                            .find(|l| !l.trim().is_empty())
+                            .map(|l| format!(": {l}"))
+                            .unwrap_or_default();
+                        error!("Check script of {code} {reason}{last_line}");
+                        context.outcome.record(
+                            StageKind::Script,
+                            started_at,
+                            StageStatus::Failed,
+                            Some(format!("{} {reason}{last_line}", script.command)),
+                        );
+                    }
+                    context.outcome.set_script(script_outcome);
+                }
+                Err(err) => {
+                    error!("Unable to run check script of {code}: {err}");
+                    if let Error::Timeout { .. } = err {
+                        if let Some(script_outcome) = output.timed_out(&script) {
+                            context.outcome.set_script(script_outcome);
+                        }
+                    }
+                    context
+                        .outcome
+                        .record_error(StageKind::Script, started_at, &err);
+                    context.errors.push(Box::new(err));
+                }
+            }
+            Flow::Continue
+        })
+    }
+}
@@ -0,0 +1,109 @@
+use super::{timeout, Context, Flow, Stage};
+use crate::{
+    outcome::{StageKind, StageStatus},
+    smoke::{SmokeService, SmokeTests},
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::{collections::HashMap, time::Duration};
+use tracing::{error, info};
+
+/// Runs the requests maintainers wrote for the template in [`crate::Config::smoke_tests_dir`]
+pub struct RunSmokeTests;
+
+impl Stage for RunSmokeTests {
+    fn kind(&self) -> StageKind {
+        StageKind::SmokeTests
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            let code = context.template.code().clone();
+            let started_at = Utc::now();
+            match SmokeTests::load(&context.config.smoke_tests_dir, &code).await {
+                Ok(Some(smoke_tests)) if context.built => {
+                    info!("Running {} smoke tests for {code}", smoke_tests.tests.len());
+                    let run_tests = async {
+                        let mut variables = context.variables_by_service().await?;
+                        let smoke_services: HashMap<_, _> = context
+                            .services
+                            .iter()
+                            .flat_map(|s| s.instances().iter().map(move |i| (s.name(), i)))
+                            .map(|(name, instance)| {
+                                let smoke_service = SmokeService {
+                                    static_url: instance.static_url().clone(),
+                                    variables: variables.remove(name).unwrap_or_default(),
+                                };
+                                (name.clone(), smoke_service)
+                            })
+                            .collect();
+
+                        let client = reqwest::Client::new();
+                        let mut smoke_outcomes = Vec::new();
+                        for test in &smoke_tests.tests {
+                            smoke_outcomes.push(test.run(&client, &smoke_services).await);
+                        }
+                        Ok(smoke_outcomes)
+                    };
+
+                    match timeout(
+                        StageKind::SmokeTests,
+                        Duration::from_secs(context.config.timeouts.smoke_tests),
+                        run_tests,
+                    )
+                    .await
+                    {
+                        Ok(smoke_outcomes) => {
+                            let failed: Vec<_> = smoke_outcomes
+                                .iter()
+                                .filter(|t| !t.is_passed())
+                                .map(|t| t.name.clone())
+                                .collect();
+                            for smoke_outcome in smoke_outcomes {
+                                context.outcome.push_smoke_test(smoke_outcome);
+                            }
+
+                            if failed.is_empty() {
+                                context.outcome.record(
+                                    StageKind::SmokeTests,
+                                    started_at,
+                                    StageStatus::Passed,
+                                    None,
+                                );
+                            } else {
+                                error!("Failed smoke tests for {code}: {}", failed.join(", "));
+                                context.outcome.record(
+                                    StageKind::SmokeTests,
+                                    started_at,
+                                    StageStatus::Failed,
+                                    Some(format!("failed: {}", failed.join(", "))),
+                                );
+                            }
+                        }
+                        Err(err) => {
+                            error!("Unable to run smoke tests for {code}: {err}");
+                            context
+                                .outcome
+                                .record_error(StageKind::SmokeTests, started_at, &err);
+                            context.errors.push(Box::new(err));
+                        }
+                    }
+                }
+                Ok(_) => context.outcome.record(
+                    StageKind::SmokeTests,
+                    started_at,
+                    StageStatus::Skipped,
+                    None,
+                ),
+                Err(err) => {
+                    error!("Unable to load smoke tests for {code}: {err}");
+                    context
+                        .outcome
+                        .record_error(StageKind::SmokeTests, started_at, &err);
+                    context.errors.push(Box::new(err));
+                }
+            }
+            Flow::Continue
+        })
+    }
+}
@@ -0,0 +1,103 @@
+use super::{Context, Flow, Stage};
+use crate::{
+    outcome::{StageKind, StageStatus},
+    stability::Stability,
+};
+use chrono::Utc;
+use futures_util::future::{join_all, BoxFuture};
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Watches the deployed services for crashes and restarts over the stability window
+pub struct WatchStability;
+
+impl Stage for WatchStability {
+    fn kind(&self) -> StageKind {
+        StageKind::Stability
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            let code = context.template.code().clone();
+            let started_at = Utc::now();
+            let window = Duration::from_secs(context.config.stability_window);
+            let built = context.built;
+            let watched: Vec<_> = context
+                .service_outcomes
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| built && s.status.as_ref().is_some_and(|s| s.is_success()))
+                .filter_map(|(i, s)| Some((i, s.deployment_id.clone()?)))
+                .collect();
+            if window.is_zero() || watched.is_empty() {
+                context.outcome.record(
+                    StageKind::Stability,
+                    started_at,
+                    StageStatus::Skipped,
+                    None,
+                );
+                return Flow::Continue;
+            }
+
+            info!(
+                "Watching {} services of {code} for {}s",
+                watched.len(),
+                window.as_secs()
+            );
+            let token = context.token.as_str();
+            let results = join_all(
+                watched
+                    .iter()
+                    .map(|(_, deployment_id)| Stability::watch(token, deployment_id, window)),
+            )
+            .await;
+
+            let mut unstable = Vec::new();
+            let mut stability_error = None;
+            for ((i, _), result) in watched.into_iter().zip(results) {
+                let service = &mut context.service_outcomes[i];
+                match result {
+                    Ok(stability) => {
+                        if !stability.is_stable() {
+                            unstable.push(format!(
+                                "{} crashed {} time(s) and restarted {} time(s)",
+                                service.name,
+                                stability.crashes.len(),
+                                stability.restarts
+                            ));
+                        }
+                        service.stability = Some(stability);
+                    }
+                    Err(err) => {
+                        error!("Unable to watch {} for stability: {err}", service.name);
+                        stability_error = Some(err);
+                    }
+                }
+            }
+
+            if let Some(err) = stability_error {
+                context
+                    .outcome
+                    .record_error(StageKind::Stability, started_at, &err);
+                context.errors.push(Box::new(err));
+            } else if !unstable.is_empty() {
+                error!("Unstable services in {code}: {}", unstable.join(", "));
+                context.outcome.record(
+                    StageKind::Stability,
+                    started_at,
+                    StageStatus::Failed,
+                    Some(format!(
+                        "not up for the whole {}s: {}",
+                        window.as_secs(),
+                        unstable.join(", ")
+                    )),
+                );
+            } else {
+                context
+                    .outcome
+                    .record(StageKind::Stability, started_at, StageStatus::Passed, None);
+            }
+            Flow::Continue
+        })
+    }
+}
@@ -0,0 +1,90 @@
+use super::{stage_error, Context, Flow, Stage};
+use crate::{
+    outcome::{StageKind, StageStatus},
+    Error, RunEvent, Workflow, WorkflowStatus,
+};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Waits for Railway to finish setting the project up
+pub struct WaitWorkflow;
+
+impl Stage for WaitWorkflow {
+    fn kind(&self) -> StageKind {
+        StageKind::Workflow
+    }
+
+    fn run<'a>(&'a self, context: &'a mut Context) -> BoxFuture<'a, Flow> {
+        Box::pin(async move {
+            let Some(deployed) = &context.deployed else {
+                return Flow::Stop;
+            };
+
+            info!("Checking workflow for {}", context.template.code());
+            let started_at = Utc::now();
+            let Some(workflow_id) = deployed.workflow_id() else {
+                error!("No workflow id for {}", context.template.code());
+                context.outcome.record(
+                    StageKind::Workflow,
+                    started_at,
+                    StageStatus::Failed,
+                    Some("No workflow id".to_owned()),
+                );
+                return Flow::Stop;
+            };
+
+            match Workflow::status(
+                &context.token,
+                workflow_id,
+                Duration::from_secs(context.config.timeouts.workflow),
+            )
+            .await
+            .map_err(|err| stage_error(StageKind::Workflow, err))
+            {
+                Ok(WorkflowStatus::Complete) => {
+                    context.emit(RunEvent::WorkflowComplete {
+                        template: context.template.code().clone(),
+                        project_id: deployed.project_id().clone(),
+                        error: None,
+                    });
+                    context.outcome.record(
+                        StageKind::Workflow,
+                        started_at,
+                        StageStatus::Passed,
+                        None,
+                    );
+                    Flow::Continue
+                }
+                Ok(WorkflowStatus::Error(err)) => {
+                    error!("Unable to process {}: {err}", context.template.code());
+                    context.emit(RunEvent::WorkflowComplete {
+                        template: context.template.code().clone(),
+                        project_id: deployed.project_id().clone(),
+                        error: Some(err.clone()),
+                    });
+                    context.outcome.record(
+                        StageKind::Workflow,
+                        started_at,
+                        StageStatus::Failed,
+                        Some(err.clone()),
+                    );
+                    context.errors.push(Box::new(Error::Workflow(err)));
+                    Flow::Stop
+                }
+                Err(err) => {
+                    error!(
+                        "Discarded Template {} because of error: {err}",
+                        context.template.code()
+                    );
+                    context
+                        .outcome
+                        .record_error(StageKind::Workflow, started_at, &err);
+                    context.errors.push(Box::new(err));
+                    Flow::Stop
+                }
+            }
+        })
+    }
+}
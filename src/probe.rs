@@ -0,0 +1,377 @@
+pub mod mongodb;
+pub mod mysql;
+pub mod postgres;
+pub mod redis;
+
+pub use mongodb::MongoDb;
+pub use mysql::MySql;
+pub use postgres::Postgres;
+pub use redis::Redis;
+
+use crate::{config::ProbeConfig, outcome::ProbeOutcome, Error, Result};
+use futures_util::future::BoxFuture;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{net::TcpStream, time::Instant};
+use tracing::{debug, warn};
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// Connecting and handshaking through a proxy, authentication included
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Checks that a service actually speaks its protocol, not just that its port is open
+pub trait Probe: Send + Sync {
+    /// Name used in outcomes and in [`ProbeConfig::protocol`]
+    fn protocol(&self) -> &'static str;
+
+    /// Whether services deployed from this image name, without registry or tag, speak it
+    fn matches(&self, image: &str) -> bool;
+
+    /// Runs the protocol over a fresh connection, authenticating with the service variables
+    fn handshake<'a>(
+        &'a self,
+        stream: TcpStream,
+        variables: &'a HashMap<String, String>,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Whether [`Probe::write_sentinel`] and [`Probe::read_sentinel`] are implemented
+    fn supports_sentinel(&self) -> bool {
+        false
+    }
+
+    /// Stores `value` under `key` in the service's storage, to be read back after a redeploy
+    fn write_sentinel<'a>(
+        &'a self,
+        _stream: TcpStream,
+        _variables: &'a HashMap<String, String>,
+        _key: &'a str,
+        _value: &'a str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Err(unsupported(self.protocol())) })
+    }
+
+    /// What [`Probe::write_sentinel`] stored under `key`, `None` when it's gone
+    fn read_sentinel<'a>(
+        &'a self,
+        _stream: TcpStream,
+        _variables: &'a HashMap<String, String>,
+        _key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async { Err(unsupported(self.protocol())) })
+    }
+}
+
+/// Fallback for anything without a known protocol, connecting is all it checks
+pub struct Tcp;
+
+impl Probe for Tcp {
+    fn protocol(&self) -> &'static str {
+        "tcp"
+    }
+
+    fn matches(&self, _image: &str) -> bool {
+        false
+    }
+
+    fn handshake<'a>(
+        &'a self,
+        _stream: TcpStream,
+        _variables: &'a HashMap<String, String>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Known probes, picked by config first then by image, new protocols only need adding here
+pub struct Probes {
+    probes: Vec<Arc<dyn Probe>>,
+}
+
+impl Default for Probes {
+    fn default() -> Self {
+        Self {
+            probes: vec![
+                Arc::new(Postgres),
+                Arc::new(Redis),
+                Arc::new(MySql),
+                Arc::new(MongoDb),
+            ],
+        }
+    }
+}
+
+impl Probes {
+    pub fn select(
+        &self,
+        config: &[ProbeConfig],
+        template: &str,
+        service: &str,
+        image: Option<&str>,
+    ) -> Arc<dyn Probe> {
+        let configured = config
+            .iter()
+            .filter(|c| c.template == template)
+            .find(|c| c.service.as_deref().is_none_or(|s| s == service));
+        if let Some(configured) = configured {
+            if configured.protocol == Tcp.protocol() {
+                return Arc::new(Tcp);
+            }
+            match self
+                .probes
+                .iter()
+                .find(|p| p.protocol() == configured.protocol)
+            {
+                Some(probe) => return probe.clone(),
+                None => warn!(
+                    "Unknown probe protocol {} for {template}/{service}",
+                    configured.protocol
+                ),
+            }
+        }
+
+        image
+            .map(image_name)
+            .and_then(|image| self.probes.iter().find(|p| p.matches(image)))
+            .map_or_else(|| Arc::new(Tcp) as Arc<dyn Probe>, Arc::clone)
+    }
+
+    /// Connects and handshakes with `address` until it succeeds or `timeout` elapses
+    pub async fn check(
+        probe: &dyn Probe,
+        address: String,
+        variables: &HashMap<String, String>,
+        timeout: Duration,
+    ) -> ProbeOutcome {
+        let deadline = Instant::now() + timeout;
+
+        let mut outcome = ProbeOutcome {
+            protocol: probe.protocol().to_owned(),
+            address,
+            attempts: 0,
+            latency: None,
+            error: None,
+        };
+
+        let mut interval = tokio::time::interval(RETRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            outcome.attempts += 1;
+
+            let started = Instant::now();
+            let attempt = async {
+                let stream = TcpStream::connect(&outcome.address).await.map_err(|err| {
+                    Error::Probe(probe.protocol(), format!("unable to connect: {err}"))
+                })?;
+                probe.handshake(stream, variables).await
+            };
+            let result = tokio::time::timeout(ATTEMPT_TIMEOUT, attempt)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(Error::Probe(
+                        probe.protocol(),
+                        format!("no answer after {}s", ATTEMPT_TIMEOUT.as_secs()),
+                    ))
+                });
+            match result {
+                Ok(()) => {
+                    outcome.latency = Some(started.elapsed());
+                    outcome.error = None;
+                    return outcome;
+                }
+                Err(err) => {
+                    debug!(
+                        "{} probe for {} failed: {err}",
+                        outcome.protocol, outcome.address
+                    );
+                    outcome.error = Some(err.to_string());
+                }
+            }
+
+            if Instant::now() >= deadline {
+                warn!(
+                    "{} probe for {} never succeeded after {} attempts",
+                    outcome.protocol, outcome.address, outcome.attempts
+                );
+                return outcome;
+            }
+        }
+    }
+}
+
+/// Runs `attempt` on fresh connections until it succeeds or `timeout` elapses,
+/// services coming back from a redeploy take a while to accept connections
+pub async fn retry<'a, T>(
+    address: &str,
+    timeout: Duration,
+    mut attempt: impl FnMut(TcpStream) -> BoxFuture<'a, Result<T>>,
+) -> Result<T> {
+    let deadline = Instant::now() + timeout;
+
+    let mut interval = tokio::time::interval(RETRY_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let result = tokio::time::timeout(ATTEMPT_TIMEOUT, async {
+            attempt(TcpStream::connect(address).await?).await
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(Error::Probe(
+                "tcp",
+                format!("no answer after {}s", ATTEMPT_TIMEOUT.as_secs()),
+            ))
+        });
+
+        match result {
+            Ok(value) => return Ok(value),
+            Err(err) if Instant::now() >= deadline => return Err(err),
+            Err(err) => debug!("Attempt against {address} failed, retrying: {err}"),
+        }
+    }
+}
+
+fn unsupported(protocol: &'static str) -> Error {
+    Error::Probe(protocol, "sentinels aren't supported".to_owned())
+}
+
+/// `ghcr.io/railwayapp-templates/postgres-ssl:16` is `postgres-ssl`
+fn image_name(image: &str) -> &str {
+    let image = image.split('@').next().unwrap_or(image);
+    let name = image.rsplit('/').next().unwrap_or(image);
+    name.split(':').next().unwrap_or(name)
+}
+
+/// First of `names` set in the variables, templates don't agree on naming
+fn variable<'a>(variables: &'a HashMap<String, String>, names: &[&str]) -> Option<&'a str> {
+    names
+        .iter()
+        .filter_map(|name| variables.get(*name))
+        .map(String::as_str)
+        .find(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Connection to a peer answering `reply` to whatever it's sent, then hanging up
+    pub(crate) async fn replying(reply: Vec<u8>) -> TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(&reply).await.unwrap();
+            stream.shutdown().await.unwrap();
+            let _ = stream.read_to_end(&mut Vec::new()).await;
+        });
+        TcpStream::connect(address).await.unwrap()
+    }
+
+    /// Fails the test instead of hanging when a parser waits for bytes that never come
+    pub(crate) async fn bounded<T>(exchange: impl std::future::Future<Output = T>) -> T {
+        tokio::time::timeout(Duration::from_secs(5), exchange)
+            .await
+            .expect("exchange hung")
+    }
+
+    pub(crate) fn variables(variables: &[(&str, &str)]) -> HashMap<String, String> {
+        variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// Handshakes with a peer answering `reply` to whatever it's sent, then hanging up
+    pub(crate) async fn handshake_with(
+        probe: &dyn Probe,
+        variables: &[(&str, &str)],
+        reply: Vec<u8>,
+    ) -> Result<()> {
+        let stream = replying(reply).await;
+        bounded(probe.handshake(stream, &self::variables(variables))).await
+    }
+
+    /// Message of the [`Error::Probe`] an exchange failed with
+    pub(crate) fn probe_failure<T: std::fmt::Debug>(result: Result<T>, protocol: &str) -> String {
+        match result {
+            Err(Error::Probe(failed, message)) if failed == protocol => message,
+            other => panic!("expected a {protocol} probe failure, got {other:?}"),
+        }
+    }
+
+    async fn unused_address() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn records_latency_of_a_successful_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let outcome = Probes::check(&Tcp, address, &HashMap::new(), Duration::from_secs(30)).await;
+        assert!(outcome.is_healthy(), "{outcome:?}");
+        assert_eq!(outcome.attempts(), 1);
+        assert!(outcome.latency().is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_until_the_service_listens() {
+        let address = unused_address().await;
+        let listening = address.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(7)).await;
+            let listener = TcpListener::bind(listening).await.unwrap();
+            loop {
+                let _ = listener.accept().await;
+            }
+        });
+
+        let outcome = Probes::check(&Tcp, address, &HashMap::new(), Duration::from_secs(60)).await;
+        assert!(outcome.is_healthy(), "{outcome:?}");
+        assert_eq!(outcome.attempts(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unreachable_ports_fail_after_every_attempt() {
+        let address = unused_address().await;
+
+        let outcome = Probes::check(&Tcp, address, &HashMap::new(), Duration::from_secs(12)).await;
+        assert!(!outcome.is_healthy());
+        assert_eq!(outcome.latency(), None);
+        assert_eq!(outcome.attempts(), 4);
+        let error = outcome.error().clone().unwrap();
+        assert!(
+            error.starts_with("tcp probe failed: unable to connect"),
+            "{error}"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn silent_peers_time_out_each_attempt() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 64];
+                let _ = stream.read(&mut request).await;
+                connections.push(stream);
+            }
+        });
+
+        let started = Instant::now();
+        let outcome =
+            Probes::check(&Redis, address, &HashMap::new(), Duration::from_secs(15)).await;
+        assert!(!outcome.is_healthy());
+        assert_eq!(outcome.attempts(), 2);
+        assert_eq!(
+            outcome.error().as_deref(),
+            Some("redis probe failed: no answer after 10s")
+        );
+        assert_eq!(started.elapsed(), 2 * ATTEMPT_TIMEOUT);
+    }
+}
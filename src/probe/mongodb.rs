@@ -0,0 +1,265 @@
+use super::Probe;
+use crate::{Error, Result};
+use futures_util::future::BoxFuture;
+use std::collections::HashMap;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+const OP_MSG: i32 = 2013;
+/// `hello` replies are a few hundred bytes
+const MAX_REPLY_LEN: usize = 1024 * 1024;
+
+const BSON_DOUBLE: u8 = 0x01;
+const BSON_STRING: u8 = 0x02;
+const BSON_BOOLEAN: u8 = 0x08;
+const BSON_INT32: u8 = 0x10;
+
+/// `hello` command over `OP_MSG`, which needs no authentication
+pub struct MongoDb;
+
+impl Probe for MongoDb {
+    fn protocol(&self) -> &'static str {
+        "mongodb"
+    }
+
+    fn matches(&self, image: &str) -> bool {
+        image.contains("mongo")
+    }
+
+    fn handshake<'a>(
+        &'a self,
+        mut stream: TcpStream,
+        _variables: &'a HashMap<String, String>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let command = document(&[("hello", Value::Int32(1)), ("$db", Value::String("admin"))]);
+
+            let mut body = Vec::new();
+            body.extend_from_slice(&0u32.to_le_bytes());
+            body.push(0);
+            body.extend_from_slice(&command);
+
+            let mut message = Vec::new();
+            message.extend_from_slice(&(body.len() as i32 + 16).to_le_bytes());
+            message.extend_from_slice(&1i32.to_le_bytes());
+            message.extend_from_slice(&0i32.to_le_bytes());
+            message.extend_from_slice(&OP_MSG.to_le_bytes());
+            message.extend_from_slice(&body);
+            stream.write_all(&message).await?;
+
+            let len = stream.read_i32_le().await?;
+            let len = len
+                .checked_sub(4)
+                .and_then(|len| usize::try_from(len).ok())
+                .filter(|len| (12..=MAX_REPLY_LEN).contains(len))
+                .ok_or_else(|| failure(format!("invalid reply length {len}")))?;
+            let mut reply = vec![0; len];
+            stream.read_exact(&mut reply).await?;
+
+            let op_code = i32::from_le_bytes([reply[8], reply[9], reply[10], reply[11]]);
+            if op_code != OP_MSG {
+                return Err(failure(format!("unexpected reply op code {op_code}")));
+            }
+
+            // Flag bits, then a single kind 0 section holding the reply document
+            let document = reply
+                .get(12 + 4 + 1..)
+                .ok_or_else(|| failure("truncated reply".to_owned()))?;
+            if ok(document)? {
+                Ok(())
+            } else {
+                Err(failure("hello replied with ok: 0".to_owned()))
+            }
+        })
+    }
+}
+
+enum Value<'a> {
+    Int32(i32),
+    String(&'a str),
+}
+
+fn document(fields: &[(&str, Value)]) -> Vec<u8> {
+    let mut elements = Vec::new();
+    for (name, value) in fields {
+        match value {
+            Value::Int32(value) => {
+                elements.push(BSON_INT32);
+                elements.extend_from_slice(name.as_bytes());
+                elements.push(0);
+                elements.extend_from_slice(&value.to_le_bytes());
+            }
+            Value::String(value) => {
+                elements.push(BSON_STRING);
+                elements.extend_from_slice(name.as_bytes());
+                elements.push(0);
+                elements.extend_from_slice(&(value.len() as i32 + 1).to_le_bytes());
+                elements.extend_from_slice(value.as_bytes());
+                elements.push(0);
+            }
+        }
+    }
+
+    let mut document = Vec::with_capacity(elements.len() + 5);
+    document.extend_from_slice(&(elements.len() as i32 + 5).to_le_bytes());
+    document.extend_from_slice(&elements);
+    document.push(0);
+    document
+}
+
+/// Finds the top level `ok` field, skipping every other element
+fn ok(document: &[u8]) -> Result<bool> {
+    let truncated = || failure("truncated reply document".to_owned());
+
+    let mut position = 4;
+    loop {
+        let kind = *document.get(position).ok_or_else(truncated)?;
+        if kind == 0 {
+            return Err(failure("reply without ok".to_owned()));
+        }
+        position += 1;
+
+        let name_end = document[position..]
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(truncated)?;
+        let name = &document[position..position + name_end];
+        position += name_end + 1;
+
+        let value = document.get(position..).ok_or_else(truncated)?;
+        let int32 = |bytes: &[u8]| -> Result<i32> {
+            Ok(i32::from_le_bytes(
+                bytes
+                    .get(..4)
+                    .ok_or_else(truncated)?
+                    .try_into()
+                    .unwrap_or_default(),
+            ))
+        };
+        if name == b"ok" {
+            return match kind {
+                BSON_DOUBLE => Ok(f64::from_le_bytes(
+                    value
+                        .get(..8)
+                        .ok_or_else(truncated)?
+                        .try_into()
+                        .unwrap_or_default(),
+                ) == 1.0),
+                BSON_INT32 => Ok(int32(value)? == 1),
+                BSON_BOOLEAN => Ok(value.first() == Some(&1)),
+                kind => Err(failure(format!("ok has unexpected type {kind:#x}"))),
+            };
+        }
+
+        position += match kind {
+            BSON_DOUBLE | 0x09 | 0x11 | 0x12 => 8,
+            BSON_STRING | 0x0d | 0x0e => {
+                4 + usize::try_from(int32(value)?).map_err(|_| truncated())?
+            }
+            0x03 | 0x04 => usize::try_from(int32(value)?).map_err(|_| truncated())?,
+            0x05 => 4 + 1 + usize::try_from(int32(value)?).map_err(|_| truncated())?,
+            0x07 => 12,
+            BSON_BOOLEAN => 1,
+            0x0a | 0x7f | 0xff => 0,
+            BSON_INT32 => 4,
+            0x13 => 16,
+            kind => return Err(failure(format!("unsupported BSON type {kind:#x}"))),
+        };
+    }
+}
+
+fn failure(message: String) -> Error {
+    Error::Probe("mongodb", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::tests::{handshake_with, probe_failure};
+
+    fn element(kind: u8, name: &str, value: &[u8]) -> Vec<u8> {
+        let mut element = vec![kind];
+        element.extend_from_slice(name.as_bytes());
+        element.push(0);
+        element.extend_from_slice(value);
+        element
+    }
+
+    fn reply(op_code: i32, elements: &[Vec<u8>]) -> Vec<u8> {
+        let elements = elements.concat();
+        let mut body = 0u32.to_le_bytes().to_vec();
+        body.push(0);
+        body.extend_from_slice(&(elements.len() as i32 + 5).to_le_bytes());
+        body.extend_from_slice(&elements);
+        body.push(0);
+
+        let mut message = (body.len() as i32 + 16).to_le_bytes().to_vec();
+        message.extend_from_slice(&7i32.to_le_bytes());
+        message.extend_from_slice(&1i32.to_le_bytes());
+        message.extend_from_slice(&op_code.to_le_bytes());
+        message.extend_from_slice(&body);
+        message
+    }
+
+    /// Trimmed down `hello` reply, `ok` comes last as it does from a real server
+    fn hello(ok: f64) -> Vec<u8> {
+        let mut version = 7i32.to_le_bytes().to_vec();
+        version.extend_from_slice(b"7.0.12\0");
+        reply(
+            OP_MSG,
+            &[
+                element(BSON_BOOLEAN, "isWritablePrimary", &[1]),
+                element(BSON_STRING, "version", &version),
+                element(BSON_INT32, "maxWireVersion", &21i32.to_le_bytes()),
+                element(0x09, "localTime", &0i64.to_le_bytes()),
+                element(BSON_DOUBLE, "ok", &ok.to_le_bytes()),
+            ],
+        )
+    }
+
+    #[tokio::test]
+    async fn hello_replies_pass() {
+        handshake_with(&MongoDb, &[], hello(1.0)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_hellos_fail() {
+        let result = handshake_with(&MongoDb, &[], hello(0.0)).await;
+        assert_eq!(probe_failure(result, "mongodb"), "hello replied with ok: 0");
+
+        let result = handshake_with(&MongoDb, &[], reply(1, &[])).await;
+        assert_eq!(
+            probe_failure(result, "mongodb"),
+            "unexpected reply op code 1"
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_replies_fail() {
+        let result = handshake_with(&MongoDb, &[], (-1i32).to_le_bytes().to_vec()).await;
+        assert_eq!(probe_failure(result, "mongodb"), "invalid reply length -1");
+
+        let result = handshake_with(&MongoDb, &[], i32::MIN.to_le_bytes().to_vec()).await;
+        assert_eq!(
+            probe_failure(result, "mongodb"),
+            format!("invalid reply length {}", i32::MIN)
+        );
+
+        let result = handshake_with(&MongoDb, &[], reply(OP_MSG, &[])).await;
+        assert_eq!(probe_failure(result, "mongodb"), "reply without ok");
+
+        // A string claiming to run past the end of the document
+        let result = handshake_with(
+            &MongoDb,
+            &[],
+            reply(
+                OP_MSG,
+                &[element(BSON_STRING, "version", &i32::MAX.to_le_bytes())],
+            ),
+        )
+        .await;
+        assert_eq!(probe_failure(result, "mongodb"), "truncated reply document");
+    }
+}
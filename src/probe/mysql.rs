@@ -0,0 +1,353 @@
+use super::{variable, Probe};
+use crate::{Error, Result};
+use futures_util::future::BoxFuture;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+const CLIENT_LONG_PASSWORD: u32 = 0x1;
+const CLIENT_CONNECT_WITH_DB: u32 = 0x8;
+const CLIENT_PROTOCOL_41: u32 = 0x200;
+const CLIENT_SECURE_CONNECTION: u32 = 0x8000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x80000;
+const UTF8MB4: u8 = 45;
+const MAX_PACKET: u32 = 16 * 1024 * 1024;
+
+const NATIVE_PASSWORD: &str = "mysql_native_password";
+const CACHING_SHA2_PASSWORD: &str = "caching_sha2_password";
+
+/// Protocol 4.1 handshake logging in with the template's credentials
+pub struct MySql;
+
+impl Probe for MySql {
+    fn protocol(&self) -> &'static str {
+        "mysql"
+    }
+
+    fn matches(&self, image: &str) -> bool {
+        image.contains("mysql") || image.contains("mariadb")
+    }
+
+    fn handshake<'a>(
+        &'a self,
+        stream: TcpStream,
+        variables: &'a HashMap<String, String>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let user = variable(variables, &["MYSQLUSER", "MYSQL_USER"]).unwrap_or("root");
+            let password = if user == "root" {
+                variable(variables, &["MYSQL_ROOT_PASSWORD", "MYSQLPASSWORD"])
+            } else {
+                variable(variables, &["MYSQLPASSWORD", "MYSQL_PASSWORD"])
+            }
+            .unwrap_or_default();
+            let database = variable(variables, &["MYSQLDATABASE", "MYSQL_DATABASE"]);
+
+            Connection {
+                stream,
+                sequence: 0,
+            }
+            .authenticate(user, password, database)
+            .await
+        })
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    sequence: u8,
+}
+
+impl Connection {
+    async fn authenticate(
+        &mut self,
+        user: &str,
+        password: &str,
+        database: Option<&str>,
+    ) -> Result<()> {
+        let greeting = self.receive().await?;
+        if greeting.first() == Some(&0xff) {
+            return Err(error_packet(&greeting));
+        }
+        let (nonce, plugin) = parse_greeting(&greeting)?;
+
+        let mut capabilities = CLIENT_LONG_PASSWORD
+            | CLIENT_PROTOCOL_41
+            | CLIENT_SECURE_CONNECTION
+            | CLIENT_PLUGIN_AUTH;
+        if database.is_some() {
+            capabilities |= CLIENT_CONNECT_WITH_DB;
+        }
+
+        let scrambled = scramble(&plugin, password, &nonce)?;
+        let mut response = Vec::new();
+        response.extend_from_slice(&capabilities.to_le_bytes());
+        response.extend_from_slice(&MAX_PACKET.to_le_bytes());
+        response.push(UTF8MB4);
+        response.extend_from_slice(&[0; 23]);
+        response.extend_from_slice(user.as_bytes());
+        response.push(0);
+        response.push(scrambled.len() as u8);
+        response.extend_from_slice(&scrambled);
+        if let Some(database) = database {
+            response.extend_from_slice(database.as_bytes());
+            response.push(0);
+        }
+        response.extend_from_slice(plugin.as_bytes());
+        response.push(0);
+        self.send(&response).await?;
+
+        let mut switched = false;
+        loop {
+            let packet = self.receive().await?;
+            match packet.first() {
+                Some(0x00) => return Ok(()),
+                Some(0xff) => return Err(error_packet(&packet)),
+                // Auth switch request, answered once with the plugin the server asks for
+                Some(0xfe) if !switched => {
+                    switched = true;
+                    let data = &packet[1..];
+                    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+                    let plugin = String::from_utf8_lossy(&data[..end]).into_owned();
+                    let nonce = data.get(end + 1..).unwrap_or_default();
+                    let nonce = nonce.strip_suffix(&[0]).unwrap_or(nonce);
+                    self.send(&scramble(&plugin, password, nonce)?).await?;
+                }
+                // caching_sha2_password: 3 is fast auth success and an OK follows
+                Some(0x01) if packet.get(1) == Some(&3) => {}
+                // Full authentication needs TLS or the server's RSA key, the server already
+                // negotiated auth for this user which is as far as a probe needs to go
+                Some(0x01) if packet.get(1) == Some(&4) => return Ok(()),
+                _ => {
+                    return Err(failure(format!(
+                        "unexpected packet during authentication: {:02x?}",
+                        packet.iter().take(16).collect::<Vec<_>>()
+                    )))
+                }
+            }
+        }
+    }
+
+    async fn send(&mut self, payload: &[u8]) -> Result<()> {
+        let len = (payload.len() as u32).to_le_bytes();
+        let mut packet = Vec::with_capacity(payload.len() + 4);
+        packet.extend_from_slice(&len[..3]);
+        packet.push(self.sequence);
+        packet.extend_from_slice(payload);
+        self.sequence = self.sequence.wrapping_add(1);
+        self.stream.write_all(&packet).await?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Vec<u8>> {
+        let mut header = [0; 4];
+        self.stream.read_exact(&mut header).await?;
+        let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        self.sequence = header[3].wrapping_add(1);
+
+        let mut payload = vec![0; len];
+        self.stream.read_exact(&mut payload).await?;
+        Ok(payload)
+    }
+}
+
+/// Nonce and default auth plugin out of a protocol 10 handshake
+fn parse_greeting(greeting: &[u8]) -> Result<(Vec<u8>, String)> {
+    let truncated = || failure("truncated handshake".to_owned());
+    if greeting.first() != Some(&10) {
+        return Err(failure(format!(
+            "unsupported protocol version {:?}",
+            greeting.first()
+        )));
+    }
+
+    let version_end = greeting[1..]
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or_else(truncated)?
+        + 1;
+    // Connection id, then the first 8 bytes of the nonce and a filler
+    let mut position = version_end + 1 + 4;
+    let mut nonce = greeting
+        .get(position..position + 8)
+        .ok_or_else(truncated)?
+        .to_vec();
+    position += 8 + 1;
+
+    let capabilities_low = greeting.get(position..position + 2).ok_or_else(truncated)?;
+    position += 2 + 1 + 2;
+    let capabilities_high = greeting.get(position..position + 2).ok_or_else(truncated)?;
+    let capabilities = u32::from_le_bytes([
+        capabilities_low[0],
+        capabilities_low[1],
+        capabilities_high[0],
+        capabilities_high[1],
+    ]);
+    position += 2;
+
+    let nonce_len = *greeting.get(position).ok_or_else(truncated)? as usize;
+    position += 1 + 10;
+
+    if capabilities & CLIENT_SECURE_CONNECTION != 0 {
+        let len = nonce_len.saturating_sub(8).max(13);
+        let rest = greeting
+            .get(position..position + len)
+            .ok_or_else(truncated)?;
+        nonce.extend_from_slice(rest.strip_suffix(&[0]).unwrap_or(rest));
+        position += len;
+    }
+
+    let plugin = if capabilities & CLIENT_PLUGIN_AUTH != 0 {
+        let rest = greeting.get(position..).unwrap_or_default();
+        let end = rest.iter().position(|b| *b == 0).unwrap_or(rest.len());
+        String::from_utf8_lossy(&rest[..end]).into_owned()
+    } else {
+        NATIVE_PASSWORD.to_owned()
+    };
+
+    Ok((nonce, plugin))
+}
+
+fn scramble(plugin: &str, password: &str, nonce: &[u8]) -> Result<Vec<u8>> {
+    if password.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    match plugin {
+        // SHA1(password) XOR SHA1(nonce + SHA1(SHA1(password)))
+        NATIVE_PASSWORD => {
+            let hashed = Sha1::digest(password.as_bytes());
+            let double_hashed = Sha1::digest(hashed);
+            let mixed = Sha1::new()
+                .chain_update(nonce)
+                .chain_update(double_hashed)
+                .finalize();
+            Ok(hashed.iter().zip(mixed).map(|(a, b)| a ^ b).collect())
+        }
+        // SHA256(password) XOR SHA256(SHA256(SHA256(password)) + nonce)
+        CACHING_SHA2_PASSWORD => {
+            let hashed = Sha256::digest(password.as_bytes());
+            let double_hashed = Sha256::digest(hashed);
+            let mixed = Sha256::new()
+                .chain_update(double_hashed)
+                .chain_update(nonce)
+                .finalize();
+            Ok(hashed.iter().zip(mixed).map(|(a, b)| a ^ b).collect())
+        }
+        plugin => Err(failure(format!("unsupported auth plugin {plugin}"))),
+    }
+}
+
+fn error_packet(packet: &[u8]) -> Error {
+    // 0xff, error code, then `#` and a SQL state on protocol 4.1
+    let message = packet.get(3..).unwrap_or_default();
+    let message = match message.strip_prefix(b"#") {
+        Some(message) => message.get(5..).unwrap_or_default(),
+        None => message,
+    };
+    failure(String::from_utf8_lossy(message).into_owned())
+}
+
+fn failure(message: String) -> Error {
+    Error::Probe("mysql", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::tests::{handshake_with, probe_failure};
+
+    fn packet(sequence: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(sequence);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// Protocol 10 greeting offering `mysql_native_password`
+    fn greeting() -> Vec<u8> {
+        let capabilities = CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION | CLIENT_PLUGIN_AUTH;
+        let mut greeting = vec![10];
+        greeting.extend_from_slice(b"8.0.36\0");
+        greeting.extend_from_slice(&7u32.to_le_bytes());
+        greeting.extend_from_slice(b"abcdefgh\0");
+        greeting.extend_from_slice(&capabilities.to_le_bytes()[..2]);
+        greeting.push(UTF8MB4);
+        greeting.extend_from_slice(&[2, 0]);
+        greeting.extend_from_slice(&capabilities.to_le_bytes()[2..]);
+        greeting.push(21);
+        greeting.extend_from_slice(&[0; 10]);
+        greeting.extend_from_slice(b"ijklmnopqrst\0");
+        greeting.extend_from_slice(NATIVE_PASSWORD.as_bytes());
+        greeting.push(0);
+        greeting
+    }
+
+    fn ok() -> Vec<u8> {
+        packet(2, &[0, 0, 0, 2, 0, 0, 0])
+    }
+
+    fn error(message: &str) -> Vec<u8> {
+        let mut payload = vec![0xff, 0x15, 0x04];
+        payload.extend_from_slice(b"#28000");
+        payload.extend_from_slice(message.as_bytes());
+        payload
+    }
+
+    #[test]
+    fn greetings_are_parsed() {
+        let (nonce, plugin) = parse_greeting(&greeting()).unwrap();
+        assert_eq!(nonce, b"abcdefghijklmnopqrst");
+        assert_eq!(plugin, NATIVE_PASSWORD);
+    }
+
+    #[tokio::test]
+    async fn ok_packets_pass() {
+        let mut reply = packet(0, &greeting());
+        reply.extend(ok());
+        handshake_with(&MySql, &[("MYSQL_ROOT_PASSWORD", "secret")], reply)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn error_packets_fail() {
+        let mut reply = packet(0, &greeting());
+        reply.extend(packet(2, &error("Access denied for user 'root'")));
+        let result = handshake_with(&MySql, &[("MYSQL_ROOT_PASSWORD", "wrong")], reply).await;
+        assert_eq!(
+            probe_failure(result, "mysql"),
+            "Access denied for user 'root'"
+        );
+
+        let reply = packet(0, &error("Host is blocked"));
+        let result = handshake_with(&MySql, &[], reply).await;
+        assert_eq!(probe_failure(result, "mysql"), "Host is blocked");
+    }
+
+    #[tokio::test]
+    async fn malformed_greetings_fail() {
+        let mut old = greeting();
+        old[0] = 9;
+        let result = handshake_with(&MySql, &[], packet(0, &old)).await;
+        assert_eq!(
+            probe_failure(result, "mysql"),
+            "unsupported protocol version Some(9)"
+        );
+
+        let result = handshake_with(&MySql, &[], packet(0, &greeting()[..20])).await;
+        assert_eq!(probe_failure(result, "mysql"), "truncated handshake");
+    }
+
+    #[tokio::test]
+    async fn packets_cut_short_fail() {
+        let mut reply = packet(0, &greeting());
+        reply.truncate(reply.len() - 10);
+        let result = handshake_with(&MySql, &[], reply).await;
+        assert!(matches!(result, Err(Error::Io(_))), "{result:?}");
+    }
+}
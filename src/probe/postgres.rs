@@ -0,0 +1,621 @@
+use super::{variable, Probe};
+use crate::{Error, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures_util::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+const PROTOCOL_VERSION: i32 = 196608;
+const SCRAM_SHA_256: &str = "SCRAM-SHA-256";
+const SENTINEL_TABLE: &str = "crater_sentinel";
+/// Server messages are tiny until authenticated, anything bigger is not Postgres
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+/// Startup handshake authenticated with the template's credentials, supporting cleartext,
+/// MD5 and SCRAM-SHA-256 passwords
+pub struct Postgres;
+
+impl Probe for Postgres {
+    fn protocol(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn matches(&self, image: &str) -> bool {
+        ["postgres", "postgis", "timescale", "pgvector"]
+            .iter()
+            .any(|name| image.contains(name))
+    }
+
+    fn handshake<'a>(
+        &'a self,
+        stream: TcpStream,
+        variables: &'a HashMap<String, String>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { Connection::open(stream, variables).await.map(|_| ()) })
+    }
+
+    fn supports_sentinel(&self) -> bool {
+        true
+    }
+
+    fn write_sentinel<'a>(
+        &'a self,
+        stream: TcpStream,
+        variables: &'a HashMap<String, String>,
+        key: &'a str,
+        value: &'a str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut connection = Connection::open(stream, variables).await?;
+            connection
+                .query(&format!(
+                    "CREATE TABLE IF NOT EXISTS {SENTINEL_TABLE} (key text PRIMARY KEY, value text NOT NULL); \
+                     INSERT INTO {SENTINEL_TABLE} VALUES ({}, {}) \
+                     ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                    quote(key),
+                    quote(value)
+                ))
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn read_sentinel<'a>(
+        &'a self,
+        stream: TcpStream,
+        variables: &'a HashMap<String, String>,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move {
+            let mut connection = Connection::open(stream, variables).await?;
+
+            // A missing table means the whole database was lost, not an error
+            let exists = connection
+                .query(&format!(
+                    "SELECT to_regclass({}) IS NOT NULL",
+                    quote(SENTINEL_TABLE)
+                ))
+                .await?;
+            if exists.first().cloned().flatten().as_deref() != Some("t") {
+                return Ok(None);
+            }
+
+            let rows = connection
+                .query(&format!(
+                    "SELECT value FROM {SENTINEL_TABLE} WHERE key = {}",
+                    quote(key)
+                ))
+                .await?;
+            Ok(rows.into_iter().next().flatten())
+        })
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+}
+
+impl Connection {
+    async fn open(stream: TcpStream, variables: &HashMap<String, String>) -> Result<Self> {
+        let user = variable(variables, &["PGUSER", "POSTGRES_USER"]).unwrap_or("postgres");
+        let password = variable(variables, &["PGPASSWORD", "POSTGRES_PASSWORD"]);
+        let database = variable(variables, &["PGDATABASE", "POSTGRES_DB"]).unwrap_or(user);
+
+        let mut connection = Self { stream };
+        connection.authenticate(user, password, database).await?;
+        Ok(connection)
+    }
+
+    async fn authenticate(
+        &mut self,
+        user: &str,
+        password: Option<&str>,
+        database: &str,
+    ) -> Result<()> {
+        let mut startup = Vec::new();
+        startup.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        for (key, value) in [("user", user), ("database", database)] {
+            startup.extend_from_slice(key.as_bytes());
+            startup.push(0);
+            startup.extend_from_slice(value.as_bytes());
+            startup.push(0);
+        }
+        startup.push(0);
+        self.send(None, &startup).await?;
+
+        let mut scram = None;
+        loop {
+            let (tag, body) = self.receive().await?;
+            match tag {
+                b'R' => {
+                    let code =
+                        i32::from_be_bytes(field(&body, 0..4)?.try_into().unwrap_or_default());
+                    let data = &body[4..];
+                    match code {
+                        0 => {}
+                        3 => {
+                            let password = required(password)?;
+                            self.send(Some(b'p'), &nul_terminated(password)).await?;
+                        }
+                        5 => {
+                            let password = required(password)?;
+                            let hashed = format!("{:x}", md5::compute(format!("{password}{user}")));
+                            let mut salted = hashed.into_bytes();
+                            salted.extend_from_slice(field(data, 0..4)?);
+                            let response = format!("md5{:x}", md5::compute(salted));
+                            self.send(Some(b'p'), &nul_terminated(&response)).await?;
+                        }
+                        10 => {
+                            let mechanisms = String::from_utf8_lossy(data);
+                            if !mechanisms.split('\0').any(|m| m == SCRAM_SHA_256) {
+                                return Err(failure(format!(
+                                    "no supported SASL mechanism in {mechanisms:?}"
+                                )));
+                            }
+                            let client = Scram::new(required(password)?);
+                            let first = client.first_message();
+                            let mut message = nul_terminated(SCRAM_SHA_256);
+                            message.extend_from_slice(&(first.len() as i32).to_be_bytes());
+                            message.extend_from_slice(first.as_bytes());
+                            self.send(Some(b'p'), &message).await?;
+                            scram = Some(client);
+                        }
+                        11 => {
+                            let client = scram
+                                .as_mut()
+                                .ok_or_else(|| failure("unexpected SASL continue".to_owned()))?;
+                            let message = client.final_message(&String::from_utf8_lossy(data))?;
+                            self.send(Some(b'p'), message.as_bytes()).await?;
+                        }
+                        12 => {
+                            let client = scram
+                                .as_ref()
+                                .ok_or_else(|| failure("unexpected SASL final".to_owned()))?;
+                            client.verify_server(&String::from_utf8_lossy(data))?;
+                        }
+                        code => {
+                            return Err(failure(format!(
+                                "unsupported authentication method {code}"
+                            )))
+                        }
+                    }
+                }
+                b'E' => return Err(failure(error_message(&body))),
+                // Authenticated and ready for queries, which is all we wanted to know
+                b'Z' => return Ok(()),
+                // Parameter status, backend key data and notices
+                _ => {}
+            }
+        }
+    }
+
+    /// Simple query protocol, returning the first column of every row
+    async fn query(&mut self, sql: &str) -> Result<Vec<Option<String>>> {
+        self.send(Some(b'Q'), &nul_terminated(sql)).await?;
+
+        let mut rows = Vec::new();
+        let mut error = None;
+        loop {
+            let (tag, body) = self.receive().await?;
+            match tag {
+                b'D' => {
+                    let len =
+                        i32::from_be_bytes(field(&body, 2..6)?.try_into().unwrap_or_default());
+                    // A negative length is NULL
+                    let value = match usize::try_from(len) {
+                        Ok(len) => {
+                            Some(String::from_utf8_lossy(field(&body, 6..6 + len)?).into_owned())
+                        }
+                        Err(_) => None,
+                    };
+                    rows.push(value);
+                }
+                b'E' => error = Some(error_message(&body)),
+                b'Z' => {
+                    return match error {
+                        Some(error) => Err(failure(error)),
+                        None => Ok(rows),
+                    }
+                }
+                // Row descriptions, command completions and notices
+                _ => {}
+            }
+        }
+    }
+
+    async fn send(&mut self, tag: Option<u8>, body: &[u8]) -> Result<()> {
+        let mut message = Vec::with_capacity(body.len() + 5);
+        message.extend(tag);
+        message.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+        message.extend_from_slice(body);
+        self.stream.write_all(&message).await?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<(u8, Vec<u8>)> {
+        let tag = self.stream.read_u8().await?;
+        let len = self.stream.read_i32().await?;
+        let len = len
+            .checked_sub(4)
+            .and_then(|len| usize::try_from(len).ok())
+            .filter(|len| *len <= MAX_MESSAGE_LEN)
+            .ok_or_else(|| failure(format!("invalid message length {len}")))?;
+
+        let mut body = vec![0; len];
+        self.stream.read_exact(&mut body).await?;
+        Ok((tag, body))
+    }
+}
+
+/// Client side of RFC 5802 with SHA-256, channel binding isn't used without TLS
+struct Scram<'a> {
+    password: &'a str,
+    nonce: String,
+    auth_message: String,
+    salted_password: Vec<u8>,
+}
+
+impl<'a> Scram<'a> {
+    fn new(password: &'a str) -> Self {
+        let nonce = thread_rng().sample_iter(&Alphanumeric).take(24).collect();
+        Self {
+            password,
+            nonce,
+            auth_message: String::new(),
+            salted_password: Vec::new(),
+        }
+    }
+
+    fn first_message_bare(&self) -> String {
+        // Postgres ignores this user name in favour of the startup one
+        format!("n=,r={}", self.nonce)
+    }
+
+    fn first_message(&self) -> String {
+        format!("n,,{}", self.first_message_bare())
+    }
+
+    fn final_message(&mut self, server_first: &str) -> Result<String> {
+        let attribute = |name: &str| {
+            server_first
+                .split(',')
+                .find_map(|a| a.strip_prefix(name))
+                .ok_or_else(|| failure(format!("SCRAM message without {name}: {server_first}")))
+        };
+        let nonce = attribute("r=")?;
+        let salt = BASE64
+            .decode(attribute("s=")?)
+            .map_err(|err| failure(format!("invalid SCRAM salt: {err}")))?;
+        let iterations = attribute("i=")?
+            .parse::<u32>()
+            .map_err(|err| failure(format!("invalid SCRAM iterations: {err}")))?;
+        if !nonce.starts_with(&self.nonce) {
+            return Err(failure("server nonce doesn't extend ours".to_owned()));
+        }
+
+        self.salted_password = pbkdf2(self.password.as_bytes(), &salt, iterations)?;
+        let client_key = hmac(&self.salted_password, b"Client Key")?;
+        let stored_key = Sha256::digest(&client_key);
+
+        let without_proof = format!("c=biws,r={nonce}");
+        self.auth_message = format!(
+            "{},{server_first},{without_proof}",
+            self.first_message_bare()
+        );
+        let signature = hmac(&stored_key, self.auth_message.as_bytes())?;
+        let proof: Vec<u8> = client_key
+            .iter()
+            .zip(signature)
+            .map(|(key, signature)| key ^ signature)
+            .collect();
+
+        Ok(format!("{without_proof},p={}", BASE64.encode(proof)))
+    }
+
+    /// The server proves it knows the password too
+    fn verify_server(&self, server_final: &str) -> Result<()> {
+        let Some(verifier) = server_final.strip_prefix("v=") else {
+            return Err(failure(format!(
+                "SCRAM authentication failed: {server_final}"
+            )));
+        };
+
+        let server_key = hmac(&self.salted_password, b"Server Key")?;
+        let signature = hmac(&server_key, self.auth_message.as_bytes())?;
+        if BASE64.encode(signature) != verifier.trim() {
+            return Err(failure("server SCRAM signature mismatch".to_owned()));
+        }
+        Ok(())
+    }
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)?;
+    mac.update(message);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+/// PBKDF2-HMAC-SHA-256 with a single output block, which is all SCRAM needs
+fn pbkdf2(password: &[u8], salt: &[u8], iterations: u32) -> Result<Vec<u8>> {
+    let mut block = salt.to_vec();
+    block.extend_from_slice(&1u32.to_be_bytes());
+
+    let mut previous = hmac(password, &block)?;
+    let mut result = previous.clone();
+    for _ in 1..iterations {
+        previous = hmac(password, &previous)?;
+        for (result, byte) in result.iter_mut().zip(&previous) {
+            *result ^= byte;
+        }
+    }
+    Ok(result)
+}
+
+/// The `M` field of an error response, falling back to all of it
+fn error_message(body: &[u8]) -> String {
+    body.split(|b| *b == 0)
+        .find_map(|field| field.strip_prefix(b"M"))
+        .map(|message| String::from_utf8_lossy(message).into_owned())
+        .unwrap_or_else(|| String::from_utf8_lossy(body).into_owned())
+}
+
+fn field(body: &[u8], range: std::ops::Range<usize>) -> Result<&[u8]> {
+    body.get(range)
+        .ok_or_else(|| failure("truncated message".to_owned()))
+}
+
+/// Literal for values crater generated itself, still escaped in case
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn nul_terminated(value: &str) -> Vec<u8> {
+    let mut bytes = value.as_bytes().to_vec();
+    bytes.push(0);
+    bytes
+}
+
+fn required(password: Option<&str>) -> Result<&str> {
+    password.ok_or_else(|| failure("server asked for a password but none is set".to_owned()))
+}
+
+fn failure(message: String) -> Error {
+    Error::Probe("postgres", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::tests::{bounded, handshake_with, probe_failure, variables};
+    use tokio::net::TcpListener;
+
+    /// RFC 7677's exchange, with the empty user name Postgres clients send instead of `user`
+    const SCRAM_PASSWORD: &str = "pencil";
+    const SCRAM_CLIENT_NONCE: &str = "rOprNGfwEbeRWgbNEkqO";
+    const SCRAM_SERVER_NONCE: &str = "%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0";
+    const SCRAM_SALT: &str = "W22ZaJ0SNY7soEsUEjb6gQ==";
+    const SCRAM_ITERATIONS: u32 = 4096;
+
+    fn server_first(client_nonce: &str) -> String {
+        format!("r={client_nonce}{SCRAM_SERVER_NONCE},s={SCRAM_SALT},i={SCRAM_ITERATIONS}")
+    }
+
+    fn message(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![tag];
+        message.extend_from_slice(&(body.len() as i32 + 4).to_be_bytes());
+        message.extend_from_slice(body);
+        message
+    }
+
+    fn authentication(code: i32, data: &[u8]) -> Vec<u8> {
+        let mut body = code.to_be_bytes().to_vec();
+        body.extend_from_slice(data);
+        message(b'R', &body)
+    }
+
+    fn ready_for_query() -> Vec<u8> {
+        message(b'Z', b"I")
+    }
+
+    #[tokio::test]
+    async fn trusted_connections_pass() {
+        let mut reply = authentication(0, &[]);
+        reply.extend(message(b'S', b"server_version\x0016.2\0"));
+        reply.extend(ready_for_query());
+        handshake_with(&Postgres, &[], reply).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn md5_passwords_are_sent() {
+        let mut reply = authentication(5, &[1, 2, 3, 4]);
+        reply.extend(authentication(0, &[]));
+        reply.extend(ready_for_query());
+        handshake_with(&Postgres, &[("PGPASSWORD", "secret")], reply)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn error_responses_fail() {
+        let reply = message(
+            b'E',
+            b"SFATAL\0C28P01\0Mpassword authentication failed for user \"postgres\"\0\0",
+        );
+        let result = handshake_with(&Postgres, &[("PGPASSWORD", "wrong")], reply).await;
+        assert_eq!(
+            probe_failure(result, "postgres"),
+            "password authentication failed for user \"postgres\""
+        );
+    }
+
+    #[tokio::test]
+    async fn password_requests_fail_without_a_password() {
+        let result = handshake_with(&Postgres, &[], authentication(3, &[])).await;
+        assert_eq!(
+            probe_failure(result, "postgres"),
+            "server asked for a password but none is set"
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_messages_fail() {
+        let mut reply = vec![b'R'];
+        reply.extend_from_slice(&i32::MAX.to_be_bytes());
+        let result = handshake_with(&Postgres, &[], reply).await;
+        assert_eq!(
+            probe_failure(result, "postgres"),
+            format!("invalid message length {}", i32::MAX)
+        );
+
+        let mut reply = vec![b'R'];
+        reply.extend_from_slice(&i32::MIN.to_be_bytes());
+        let result = handshake_with(&Postgres, &[], reply).await;
+        assert_eq!(
+            probe_failure(result, "postgres"),
+            format!("invalid message length {}", i32::MIN)
+        );
+
+        let result = handshake_with(&Postgres, &[], message(b'R', &[0, 0])).await;
+        assert_eq!(probe_failure(result, "postgres"), "truncated message");
+    }
+
+    #[tokio::test]
+    async fn connections_closed_mid_handshake_fail() {
+        let result = handshake_with(&Postgres, &[], authentication(0, &[])).await;
+        assert!(matches!(result, Err(Error::Io(_))), "{result:?}");
+    }
+
+    #[test]
+    fn scram_matches_the_rfc_7677_exchange() {
+        let mut client = Scram {
+            password: SCRAM_PASSWORD,
+            nonce: SCRAM_CLIENT_NONCE.to_owned(),
+            auth_message: String::new(),
+            salted_password: Vec::new(),
+        };
+        assert_eq!(client.first_message(), "n,,n=,r=rOprNGfwEbeRWgbNEkqO");
+
+        let client_final = client
+            .final_message(&server_first(SCRAM_CLIENT_NONCE))
+            .unwrap();
+        assert_eq!(
+            client_final,
+            "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+             p=qvT2SWdEH5Q06albL+hjSYuUhCG7VndFyzIb7CK4n9k="
+        );
+        client
+            .verify_server("v=3HO6Qt1M4MKJrmlKaoOqLAI0/0TV0HZe7J9H3MBtSOg=")
+            .unwrap();
+
+        // The RFC's own signature covers the `user` name, not the empty one we sent
+        let result = client.verify_server("v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=");
+        assert_eq!(
+            probe_failure(result, "postgres"),
+            "server SCRAM signature mismatch"
+        );
+    }
+
+    async fn read_message(stream: &mut TcpStream, tagged: bool) -> Vec<u8> {
+        if tagged {
+            assert_eq!(stream.read_u8().await.unwrap(), b'p');
+        }
+        let len = stream.read_i32().await.unwrap();
+        let mut body = vec![0; len as usize - 4];
+        stream.read_exact(&mut body).await.unwrap();
+        body
+    }
+
+    /// Postgres asking for SCRAM-SHA-256 with [`SCRAM_PASSWORD`] as the password, checking the
+    /// client proof and ending with `server_final` instead of its signature when set
+    async fn scram_handshake(password: &str, server_final: Option<&'static str>) -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_message(&mut stream, false).await;
+            let mechanisms = format!("{SCRAM_SHA_256}\0\0");
+            let reply = authentication(10, mechanisms.as_bytes());
+            stream.write_all(&reply).await.unwrap();
+
+            let client_first = read_message(&mut stream, true).await;
+            let client_first = String::from_utf8_lossy(&client_first[SCRAM_SHA_256.len() + 5..]);
+            let client_first_bare = client_first.strip_prefix("n,,").unwrap().to_owned();
+            let client_nonce = client_first_bare.split_once("r=").unwrap().1;
+            let server_first = server_first(client_nonce);
+            let reply = authentication(11, server_first.as_bytes());
+            stream.write_all(&reply).await.unwrap();
+
+            let client_final = read_message(&mut stream, true).await;
+            let client_final = String::from_utf8(client_final).unwrap();
+            let (without_proof, proof) = client_final.split_once(",p=").unwrap();
+            let auth_message = format!("{client_first_bare},{server_first},{without_proof}");
+
+            let salt = BASE64.decode(SCRAM_SALT).unwrap();
+            let salted_password =
+                pbkdf2(SCRAM_PASSWORD.as_bytes(), &salt, SCRAM_ITERATIONS).unwrap();
+            let stored_key = Sha256::digest(hmac(&salted_password, b"Client Key").unwrap());
+            let signature = hmac(&stored_key, auth_message.as_bytes()).unwrap();
+            let client_key: Vec<u8> = BASE64
+                .decode(proof)
+                .unwrap()
+                .iter()
+                .zip(signature)
+                .map(|(proof, signature)| proof ^ signature)
+                .collect();
+            if Sha256::digest(client_key) != stored_key {
+                let error = b"SFATAL\0C28P01\0Mpassword authentication failed\0\0";
+                stream.write_all(&message(b'E', error)).await.unwrap();
+                return;
+            }
+
+            let server_key = hmac(&salted_password, b"Server Key").unwrap();
+            let verifier = BASE64.encode(hmac(&server_key, auth_message.as_bytes()).unwrap());
+            let server_final = server_final.map_or_else(|| format!("v={verifier}"), str::to_owned);
+            let mut reply = authentication(12, server_final.as_bytes());
+            reply.extend(authentication(0, &[]));
+            reply.extend(ready_for_query());
+            stream.write_all(&reply).await.unwrap();
+            let _ = stream.read_to_end(&mut Vec::new()).await;
+        });
+
+        let variables = variables(&[("PGPASSWORD", password)]);
+        let stream = TcpStream::connect(address).await.unwrap();
+        bounded(Postgres.handshake(stream, &variables)).await
+    }
+
+    #[tokio::test]
+    async fn scram_passwords_are_proven() {
+        scram_handshake(SCRAM_PASSWORD, None).await.unwrap();
+
+        let result = scram_handshake("wrong", None).await;
+        assert_eq!(
+            probe_failure(result, "postgres"),
+            "password authentication failed"
+        );
+    }
+
+    #[tokio::test]
+    async fn scram_servers_must_prove_the_password() {
+        let result = scram_handshake(
+            SCRAM_PASSWORD,
+            Some("v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4="),
+        )
+        .await;
+        assert_eq!(
+            probe_failure(result, "postgres"),
+            "server SCRAM signature mismatch"
+        );
+
+        let result = scram_handshake(SCRAM_PASSWORD, Some("e=invalid-proof")).await;
+        assert_eq!(
+            probe_failure(result, "postgres"),
+            "SCRAM authentication failed: e=invalid-proof"
+        );
+    }
+}
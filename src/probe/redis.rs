@@ -0,0 +1,245 @@
+use super::{variable, Probe};
+use crate::{Error, Result};
+use futures_util::future::BoxFuture;
+use std::collections::HashMap;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+/// Sentinels are short values crater wrote itself, a longer reply is not one of them
+const MAX_SENTINEL_LEN: usize = 64 * 1024;
+
+/// `AUTH` when the template sets a password, then `PING`
+pub struct Redis;
+
+impl Probe for Redis {
+    fn protocol(&self) -> &'static str {
+        "redis"
+    }
+
+    fn matches(&self, image: &str) -> bool {
+        ["redis", "valkey", "keydb", "dragonfly"]
+            .iter()
+            .any(|name| image.contains(name))
+    }
+
+    fn handshake<'a>(
+        &'a self,
+        stream: TcpStream,
+        variables: &'a HashMap<String, String>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut stream = authenticate(stream, variables).await?;
+            stream.write_all(&command(&["PING"])).await?;
+            expect(&mut stream, "+PONG").await
+        })
+    }
+
+    fn supports_sentinel(&self) -> bool {
+        true
+    }
+
+    fn write_sentinel<'a>(
+        &'a self,
+        stream: TcpStream,
+        variables: &'a HashMap<String, String>,
+        key: &'a str,
+        value: &'a str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut stream = authenticate(stream, variables).await?;
+            stream.write_all(&command(&["SET", key, value])).await?;
+            expect(&mut stream, "+OK").await?;
+
+            // Snapshot straight away so the check is about where the data lands on disk,
+            // not about the template's save schedule
+            stream.write_all(&command(&["SAVE"])).await?;
+            expect(&mut stream, "+OK").await
+        })
+    }
+
+    fn read_sentinel<'a>(
+        &'a self,
+        stream: TcpStream,
+        variables: &'a HashMap<String, String>,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move {
+            let mut stream = authenticate(stream, variables).await?;
+            stream.write_all(&command(&["GET", key])).await?;
+
+            let header = line(&mut stream).await?;
+            let len = match header.strip_prefix('$') {
+                Some("-1") => return Ok(None),
+                Some(len) => len
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|len| *len <= MAX_SENTINEL_LEN)
+                    .ok_or_else(|| failure(format!("invalid bulk string length {len}")))?,
+                None => return Err(reply_error(&header, "a bulk string")),
+            };
+
+            // The value is followed by a CRLF
+            let with_crlf = len
+                .checked_add(2)
+                .ok_or_else(|| failure(format!("invalid bulk string length {len}")))?;
+            let mut value = vec![0; with_crlf];
+            stream.read_exact(&mut value).await?;
+            value.truncate(len);
+            Ok(Some(String::from_utf8_lossy(&value).into_owned()))
+        })
+    }
+}
+
+/// `AUTH` when the template sets a password
+async fn authenticate(
+    stream: TcpStream,
+    variables: &HashMap<String, String>,
+) -> Result<BufReader<TcpStream>> {
+    let mut stream = BufReader::new(stream);
+
+    let password = variable(variables, &["REDISPASSWORD", "REDIS_PASSWORD"]);
+    if let Some(password) = password {
+        let user = variable(variables, &["REDISUSER", "REDIS_USER"]);
+        let auth = match user {
+            Some(user) => command(&["AUTH", user, password]),
+            None => command(&["AUTH", password]),
+        };
+        stream.write_all(&auth).await?;
+        expect(&mut stream, "+OK").await?;
+    }
+
+    Ok(stream)
+}
+
+/// RESP array of bulk strings
+fn command(args: &[&str]) -> Vec<u8> {
+    let mut command = format!("*{}\r\n", args.len());
+    for arg in args {
+        command.push_str(&format!("${}\r\n{arg}\r\n", arg.len()));
+    }
+    command.into_bytes()
+}
+
+async fn expect(stream: &mut BufReader<TcpStream>, expected: &str) -> Result<()> {
+    let line = line(stream).await?;
+    if line == expected {
+        return Ok(());
+    }
+    Err(reply_error(&line, expected))
+}
+
+async fn line(stream: &mut BufReader<TcpStream>) -> Result<String> {
+    let mut line = String::new();
+    stream.read_line(&mut line).await?;
+    Ok(line.trim_end().to_owned())
+}
+
+fn reply_error(line: &str, expected: &str) -> Error {
+    failure(match line.strip_prefix('-') {
+        Some(error) => error.to_owned(),
+        None if line.is_empty() => "connection closed".to_owned(),
+        None => format!("expected {expected}, got {line}"),
+    })
+}
+
+fn failure(message: String) -> Error {
+    Error::Probe("redis", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::tests::{bounded, handshake_with, probe_failure, replying, variables};
+
+    #[tokio::test]
+    async fn pong_passes() {
+        handshake_with(&Redis, &[], b"+PONG\r\n".to_vec())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn authenticates_before_pinging() {
+        handshake_with(
+            &Redis,
+            &[("REDISPASSWORD", "secret")],
+            b"+OK\r\n+PONG\r\n".to_vec(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn error_replies_fail() {
+        let result = handshake_with(
+            &Redis,
+            &[],
+            b"-NOAUTH Authentication required.\r\n".to_vec(),
+        )
+        .await;
+        assert_eq!(
+            probe_failure(result, "redis"),
+            "NOAUTH Authentication required."
+        );
+    }
+
+    #[tokio::test]
+    async fn unexpected_replies_fail() {
+        let result = handshake_with(&Redis, &[], b"HTTP/1.1 400 Bad Request\r\n".to_vec()).await;
+        assert_eq!(
+            probe_failure(result, "redis"),
+            "expected +PONG, got HTTP/1.1 400 Bad Request"
+        );
+
+        let result = handshake_with(&Redis, &[], Vec::new()).await;
+        assert_eq!(probe_failure(result, "redis"), "connection closed");
+    }
+
+    #[tokio::test]
+    async fn sentinels_are_saved_to_disk() {
+        let stream = replying(b"+OK\r\n+OK\r\n".to_vec()).await;
+        bounded(Redis.write_sentinel(stream, &variables(&[]), "crater", "abc"))
+            .await
+            .unwrap();
+
+        let stream =
+            replying(b"+OK\r\n-ERR Background save already in progress\r\n".to_vec()).await;
+        let result = bounded(Redis.write_sentinel(stream, &variables(&[]), "crater", "abc")).await;
+        assert_eq!(
+            probe_failure(result, "redis"),
+            "ERR Background save already in progress"
+        );
+    }
+
+    #[tokio::test]
+    async fn sentinels_are_read_back() {
+        let stream = replying(b"$3\r\nabc\r\n".to_vec()).await;
+        let value = bounded(Redis.read_sentinel(stream, &variables(&[]), "crater")).await;
+        assert_eq!(value.unwrap().as_deref(), Some("abc"));
+
+        let stream = replying(b"$-1\r\n".to_vec()).await;
+        let value = bounded(Redis.read_sentinel(stream, &variables(&[]), "crater")).await;
+        assert_eq!(value.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn oversized_sentinels_fail() {
+        for len in [usize::MAX.to_string(), (MAX_SENTINEL_LEN + 1).to_string()] {
+            let stream = replying(format!("${len}\r\n").into_bytes()).await;
+            let result = bounded(Redis.read_sentinel(stream, &variables(&[]), "crater")).await;
+            assert_eq!(
+                probe_failure(result, "redis"),
+                format!("invalid bulk string length {len}")
+            );
+        }
+
+        let stream = replying(b"$-5\r\n".to_vec()).await;
+        let result = bounded(Redis.read_sentinel(stream, &variables(&[]), "crater")).await;
+        assert_eq!(
+            probe_failure(result, "redis"),
+            "invalid bulk string length -5"
+        );
+    }
+}
@@ -1,13 +1,50 @@
-use crate::{Error, Result};
+//! Typed wrappers over Railway's public GraphQL API, every call takes the API token
+//! it runs with
+
+use crate::{metrics, Error, Result};
 use serde::{Deserialize, Serialize};
-use tracing::trace;
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+use tracing::{field, info_span, trace, Instrument};
 
 pub mod deployment;
+pub mod environment;
+#[cfg(test)]
+pub(crate) mod fake;
 pub mod project;
 pub mod service;
+pub mod subscription;
 pub mod template;
+pub mod volume;
 pub mod workflow;
 
+pub const URL: &str = "https://backboard.railway.app/graphql/v2";
+pub const WS_URL: &str = "wss://backboard.railway.app/graphql/v2";
+
+tokio::task_local! {
+    static ENDPOINTS: Endpoints;
+}
+
+/// Where the API is reached, Railway's own unless overridden with [`Railway::scoped`]
+#[derive(Clone, Debug)]
+pub(crate) struct Endpoints {
+    pub http: String,
+    pub ws: String,
+}
+
+impl Endpoints {
+    fn current() -> Self {
+        ENDPOINTS
+            .try_with(Endpoints::clone)
+            .unwrap_or_else(|_| Self {
+                http: URL.to_owned(),
+                ws: WS_URL.to_owned(),
+            })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RailwayError {
     pub message: String,
@@ -22,24 +59,54 @@ pub struct RailwayResponse<T> {
     pub errors: Vec<RailwayError>,
 }
 
+/// Entry point for queries and mutations the typed wrappers don't cover
 pub struct Railway;
 
 impl Railway {
+    /// Runs a `{"query": ..., "variables": {...}}` body, failing on any GraphQL error
     pub async fn query<T: serde::de::DeserializeOwned + std::fmt::Debug>(
         token: &str,
         json: serde_json::Value,
+    ) -> Result<T> {
+        let operation = operation_name(&json).to_owned();
+        let span = info_span!(
+            "railway.query",
+            otel.name = %format!("railway {operation}"),
+            otel.kind = "client",
+            otel.status_code = field::Empty,
+            graphql.operation.name = %operation,
+        );
+        let started = Instant::now();
+        let result = Self::send(token, json).instrument(span.clone()).await;
+        metrics::record_railway_request(&operation, started.elapsed(), result.is_err());
+        if result.is_err() {
+            span.record("otel.status_code", "ERROR");
+        }
+        result
+    }
+
+    /// Runs `future` against `endpoints`, e.g. a local stand-in for Railway. Tasks it spawns
+    /// still talk to Railway
+    #[cfg(test)]
+    pub(crate) async fn scoped<F: Future>(endpoints: Endpoints, future: F) -> F::Output {
+        ENDPOINTS.scope(endpoints, future).await
+    }
+
+    async fn send<T: serde::de::DeserializeOwned + std::fmt::Debug>(
+        token: &str,
+        json: serde_json::Value,
     ) -> Result<T> {
         trace!("Executing query: {json:#?}");
 
-        let url = "https://backboard.railway.app/graphql/v2";
+        let url = Endpoints::current().http;
         let response = reqwest::Client::new()
-            .post(url)
+            .post(&url)
             .header("Authorization", format!("Bearer {token}"))
             .json(&json)
             .fetch_mode_no_cors()
             .send()
             .await
-            .map_err(|err| Error::RailwayFailure(err, url, json.clone()))?;
+            .map_err(|err| Error::RailwayFailure(err, url.clone(), json.clone()))?;
 
         let status = response.status();
         if status != 200 {
@@ -71,3 +138,26 @@ impl Railway {
         }
     }
 }
+
+/// Fails with [`Error::RailwayTimeout`] if `future` doesn't finish within `deadline`
+pub(crate) async fn within<T>(
+    deadline: Duration,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    tokio::time::timeout(deadline, future)
+        .await
+        .map_err(|_| Error::RailwayTimeout(deadline))?
+}
+
+/// Name the GraphQL document gives its operation, `buildLogs` for `query buildLogs(...)`
+fn operation_name(json: &serde_json::Value) -> &str {
+    let mut words = json["query"]
+        .as_str()
+        .unwrap_or_default()
+        .split(|c: char| c == '(' || c == '{' || c.is_whitespace())
+        .filter(|word| !word.is_empty());
+    match (words.next(), words.next()) {
+        (Some("query" | "mutation" | "subscription"), Some(name)) => name,
+        _ => "anonymous",
+    }
+}
@@ -1,29 +1,401 @@
-use crate::{Railway, Result};
+use crate::{railway::subscription::Subscription, Error, Railway, Result};
+use chrono::{DateTime, Utc};
 use derive_get::Getters;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::warn;
 
 const BUILD_LOGS: &str = include_str!("../graphql/deployment_build_logs.gql");
+const CANCEL: &str = include_str!("../graphql/deployment_cancel.gql");
+const LOGS: &str = include_str!("../graphql/deployment_logs.gql");
+const EVENTS: &str = include_str!("../graphql/deployment_events.gql");
+const REDEPLOY: &str = include_str!("../graphql/deployment_redeploy.gql");
+const RESTART: &str = include_str!("../graphql/deployment_restart.gql");
+const STATUS_SUBSCRIPTION: &str = include_str!("../graphql/deployment_status_subscription.gql");
+const BUILD_LOGS_SUBSCRIPTION: &str =
+    include_str!("../graphql/deployment_build_logs_subscription.gql");
+const LOGS_SUBSCRIPTION: &str = include_str!("../graphql/deployment_logs_subscription.gql");
+/// A log subscription silent for this long after its deployment settled has caught up
+const LOGS_IDLE: Duration = Duration::from_secs(3);
+const STATUS_POLL_INTERVAL: Duration = Duration::from_secs(10);
 
-#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+/// Log lines followed over a subscription
+#[derive(Debug, Clone, Default)]
+pub struct FollowedLogs {
+    pub logs: Vec<DeploymentLog>,
+    /// Set when the deadline passed before the deployment settled and its logs went quiet
+    pub truncated: bool,
+}
+
+/// Log levels as reported by Railway, ordered from least to most severe
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// Apps pick their own level names, anything unrecognized is informational
+impl From<&str> for Severity {
+    fn from(severity: &str) -> Self {
+        match severity.to_ascii_lowercase().as_str() {
+            "trace" | "debug" => Self::Debug,
+            "warn" | "warning" => Self::Warn,
+            "err" | "error" | "fatal" | "critical" | "panic" => Self::Error,
+            _ => Self::Info,
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Severity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Severity {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|s| Self::from(s.as_str()))
+    }
+}
+
+/// Shape of a log line as Railway sends it
+#[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
+struct RawDeploymentLog {
+    message: String,
+    severity: Option<Severity>,
+    timestamp: DateTime<Utc>,
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "RawDeploymentLog")]
 pub struct DeploymentLog {
     message: String,
-    severity: Option<String>,
-    timestamp: String,
+    #[copy]
+    severity: Severity,
+    #[copy]
+    timestamp: DateTime<Utc>,
+    /// Fields of lines the app logged as a JSON object, empty otherwise
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+impl From<RawDeploymentLog> for DeploymentLog {
+    fn from(raw: RawDeploymentLog) -> Self {
+        let attributes = match serde_json::from_str(raw.message.trim()) {
+            Ok(serde_json::Value::Object(attributes)) => attributes,
+            _ => serde_json::Map::new(),
+        };
+
+        Self {
+            message: raw.message,
+            severity: raw.severity.unwrap_or_default(),
+            timestamp: raw.timestamp,
+            attributes,
+        }
+    }
+}
+
+impl DeploymentLog {
+    pub fn attribute(&self, key: &str) -> Option<&serde_json::Value> {
+        self.attributes.get(key)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Inclusive on both ends
+    pub fn is_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+        from <= self.timestamp && self.timestamp <= to
+    }
+
+    pub fn errors(logs: &[Self]) -> impl Iterator<Item = &Self> {
+        logs.iter().filter(|log| log.is_error())
+    }
+
+    pub fn at_least(logs: &[Self], severity: Severity) -> impl Iterator<Item = &Self> {
+        logs.iter().filter(move |log| log.severity >= severity)
+    }
+
+    pub fn between(
+        logs: &[Self],
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Iterator<Item = &Self> {
+        logs.iter().filter(move |log| log.is_between(from, to))
+    }
+}
+
+/// Every state a Railway deployment can be in, unknown states are kept verbatim
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeploymentStatus {
+    Building,
+    Crashed,
+    Deploying,
+    Failed,
+    Initializing,
+    NeedsApproval,
+    Queued,
+    Removed,
+    Removing,
+    Skipped,
+    Sleeping,
+    Success,
+    Waiting,
+    Unknown(String),
+}
+
+impl DeploymentStatus {
+    /// The deployment won't change state on its own anymore
+    pub fn is_terminal(&self) -> bool {
+        !matches!(
+            self,
+            Self::Building
+                | Self::Deploying
+                | Self::Initializing
+                | Self::Queued
+                | Self::Removing
+                | Self::Waiting
+        )
+    }
+
+    /// Built and running, sleeping services are healthy services with no traffic
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Success | Self::Sleeping)
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Building => "BUILDING",
+            Self::Crashed => "CRASHED",
+            Self::Deploying => "DEPLOYING",
+            Self::Failed => "FAILED",
+            Self::Initializing => "INITIALIZING",
+            Self::NeedsApproval => "NEEDS_APPROVAL",
+            Self::Queued => "QUEUED",
+            Self::Removed => "REMOVED",
+            Self::Removing => "REMOVING",
+            Self::Skipped => "SKIPPED",
+            Self::Sleeping => "SLEEPING",
+            Self::Success => "SUCCESS",
+            Self::Waiting => "WAITING",
+            Self::Unknown(status) => status,
+        }
+    }
+}
+
+impl From<String> for DeploymentStatus {
+    fn from(status: String) -> Self {
+        match status.as_str() {
+            "BUILDING" => Self::Building,
+            "CRASHED" => Self::Crashed,
+            "DEPLOYING" => Self::Deploying,
+            "FAILED" => Self::Failed,
+            "INITIALIZING" => Self::Initializing,
+            "NEEDS_APPROVAL" => Self::NeedsApproval,
+            "QUEUED" => Self::Queued,
+            "REMOVED" => Self::Removed,
+            "REMOVING" => Self::Removing,
+            "SKIPPED" => Self::Skipped,
+            "SLEEPING" => Self::Sleeping,
+            "SUCCESS" => Self::Success,
+            "WAITING" => Self::Waiting,
+            _ => Self::Unknown(status),
+        }
+    }
+}
+
+impl std::fmt::Display for DeploymentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for DeploymentStatus {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for DeploymentStatus {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentPhase {
+    Build,
+    Deploy,
+    Other,
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentEvent {
+    step: String,
+    created_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+    error: Option<String>,
+}
+
+impl DeploymentEvent {
+    pub fn phase(&self) -> DeploymentPhase {
+        match self.step.as_str() {
+            "SNAPSHOT_CODE" | "BUILD_IMAGE" | "PUBLISH_IMAGE" => DeploymentPhase::Build,
+            "WAIT_FOR_DEPENDENCIES"
+            | "PRE_DEPLOY_COMMAND"
+            | "MIGRATE_VOLUMES"
+            | "CREATE_CONTAINER"
+            | "HEALTHCHECK"
+            | "DRAIN_INSTANCES" => DeploymentPhase::Deploy,
+            _ => DeploymentPhase::Other,
+        }
+    }
+}
+
+/// Every step a deployment went through, as reported by the API
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct DeploymentTimeline {
+    created_at: DateTime<Utc>,
+    status: DeploymentStatus,
+    status_updated_at: Option<DateTime<Utc>>,
+    events: Vec<DeploymentEvent>,
+}
+
+impl DeploymentTimeline {
+    fn phase(&self, phase: DeploymentPhase) -> impl Iterator<Item = &DeploymentEvent> {
+        self.events.iter().filter(move |e| e.phase() == phase)
+    }
+
+    pub fn build_started(&self) -> Option<DateTime<Utc>> {
+        self.phase(DeploymentPhase::Build)
+            .map(|e| e.created_at)
+            .min()
+    }
+
+    pub fn build_finished(&self) -> Option<DateTime<Utc>> {
+        self.phase(DeploymentPhase::Build)
+            .map(|e| e.completed_at)
+            .max()
+            .flatten()
+    }
+
+    pub fn deploy_started(&self) -> Option<DateTime<Utc>> {
+        self.phase(DeploymentPhase::Deploy)
+            .map(|e| e.created_at)
+            .min()
+    }
+
+    /// When the deployment became active, if it ever did
+    pub fn active_at(&self) -> Option<DateTime<Utc>> {
+        if !self.status.is_success() {
+            return None;
+        }
+
+        self.phase(DeploymentPhase::Deploy)
+            .map(|e| e.completed_at)
+            .max()
+            .flatten()
+            .or(self.status_updated_at)
+    }
+
+    pub fn crashed_at(&self) -> Option<DateTime<Utc>> {
+        (self.status == DeploymentStatus::Crashed)
+            .then_some(self.status_updated_at)
+            .flatten()
+    }
+
+    /// Time spent building the image, from the first build step to the last one
+    pub fn build_duration(&self) -> Option<Duration> {
+        duration(self.build_started()?, self.build_finished()?)
+    }
+
+    /// Time from the start of the deploy until the deployment became active
+    pub fn deploy_duration(&self) -> Option<Duration> {
+        duration(self.deploy_started()?, self.active_at()?)
+    }
+}
+
+fn duration(from: DateTime<Utc>, to: DateTime<Utc>) -> Option<Duration> {
+    (to - from).to_std().ok()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentStatusEvent {
+    deployment: DeploymentStatusEventDeployment,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DeploymentStatusEventDeployment {
+    status: DeploymentStatus,
+}
+
+impl DeploymentStatusEvent {
+    pub fn status(&self) -> &DeploymentStatus {
+        &self.deployment.status
+    }
+}
+
+/// A batch of build or deploy logs pushed by a log subscription
+#[derive(Deserialize, Debug, Clone)]
+pub struct DeploymentLogsEvent {
+    #[serde(alias = "buildLogs", alias = "deploymentLogs")]
+    logs: Vec<DeploymentLog>,
+}
+
+impl DeploymentLogsEvent {
+    pub fn logs(self) -> Vec<DeploymentLog> {
+        self.logs
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Deployment;
 
 impl Deployment {
+    /// Build logs so far, they stop growing once the build finishes
     pub async fn build_logs(token: &str, deployment_id: &str) -> Result<Vec<DeploymentLog>> {
+        Self::build_logs_since(token, deployment_id, None).await
+    }
+
+    /// Build logs from `since` onwards, inclusive, so callers paging with it must dedupe
+    pub async fn build_logs_since(
+        token: &str,
+        deployment_id: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<DeploymentLog>> {
         let response: DeploymentLogResponse = Railway::query(
             token,
             serde_json::json!({
                 "query": BUILD_LOGS,
                 "variables": {
                     "deploymentId": deployment_id,
+                    "startDate": since,
                 }
             }),
         )
@@ -37,4 +409,472 @@ impl Deployment {
 
         Ok(response.build_logs)
     }
+
+    /// Deploy logs so far, the running app keeps adding to them
+    pub async fn logs(token: &str, deployment_id: &str) -> Result<Vec<DeploymentLog>> {
+        let response: DeploymentLogResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": LOGS,
+                "variables": {
+                    "deploymentId": deployment_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentLogResponse {
+            deployment_logs: Vec<DeploymentLog>,
+        }
+
+        Ok(response.deployment_logs)
+    }
+
+    /// Current status and lifecycle events of a deployment
+    pub async fn timeline(token: &str, deployment_id: &str) -> Result<DeploymentTimeline> {
+        let response: DeploymentEventsResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": EVENTS,
+                "variables": {
+                    "id": deployment_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentEventsDeployment {
+            created_at: DateTime<Utc>,
+            status: DeploymentStatus,
+            status_updated_at: Option<DateTime<Utc>>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentEventsEdgeNodePayload {
+            error: Option<String>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentEventsEdgeNode {
+            step: String,
+            created_at: DateTime<Utc>,
+            completed_at: Option<DateTime<Utc>>,
+            payload: Option<DeploymentEventsEdgeNodePayload>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentEventsEdge {
+            node: DeploymentEventsEdgeNode,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentEvents {
+            edges: Vec<DeploymentEventsEdge>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentEventsResponse {
+            deployment: DeploymentEventsDeployment,
+            deployment_events: DeploymentEvents,
+        }
+
+        let mut events: Vec<_> = response
+            .deployment_events
+            .edges
+            .into_iter()
+            .map(|e| DeploymentEvent {
+                step: e.node.step,
+                created_at: e.node.created_at,
+                completed_at: e.node.completed_at,
+                error: e.node.payload.and_then(|p| p.error),
+            })
+            .collect();
+        events.sort_by_key(|e| e.created_at);
+
+        Ok(DeploymentTimeline {
+            created_at: response.deployment.created_at,
+            status: response.deployment.status,
+            status_updated_at: response.deployment.status_updated_at,
+            events,
+        })
+    }
+
+    /// Streams every status change from now on
+    pub async fn subscribe_status(
+        token: &str,
+        deployment_id: &str,
+    ) -> Result<Subscription<DeploymentStatusEvent>> {
+        Subscription::start(
+            token,
+            serde_json::json!({
+                "query": STATUS_SUBSCRIPTION,
+                "variables": {
+                    "id": deployment_id,
+                }
+            }),
+        )
+        .await
+    }
+
+    /// Streams build log lines as they are written
+    pub async fn subscribe_build_logs(
+        token: &str,
+        deployment_id: &str,
+    ) -> Result<Subscription<DeploymentLogsEvent>> {
+        Subscription::start(
+            token,
+            serde_json::json!({
+                "query": BUILD_LOGS_SUBSCRIPTION,
+                "variables": {
+                    "deploymentId": deployment_id,
+                }
+            }),
+        )
+        .await
+    }
+
+    /// Streams deploy log lines as they are written
+    pub async fn subscribe_logs(
+        token: &str,
+        deployment_id: &str,
+    ) -> Result<Subscription<DeploymentLogsEvent>> {
+        Subscription::start(
+            token,
+            serde_json::json!({
+                "query": LOGS_SUBSCRIPTION,
+                "variables": {
+                    "deploymentId": deployment_id,
+                }
+            }),
+        )
+        .await
+    }
+
+    /// Build logs over a subscription until the deployment settles or `deadline` passes,
+    /// falling back to [`Self::build_logs`] when unavailable
+    pub async fn follow_build_logs(
+        token: &str,
+        deployment_id: &str,
+        deadline: Duration,
+    ) -> Result<FollowedLogs> {
+        match Self::subscribe_build_logs(token, deployment_id).await {
+            Ok(subscription) => {
+                Self::drain_logs(token, deployment_id, subscription, deadline).await
+            }
+            Err(err) => {
+                warn!(
+                    "Unable to subscribe to build logs of {deployment_id}, querying instead: {err}"
+                );
+                Ok(FollowedLogs {
+                    logs: Self::build_logs(token, deployment_id).await?,
+                    truncated: false,
+                })
+            }
+        }
+    }
+
+    /// Deploy logs over a subscription until the deployment settles or `deadline` passes,
+    /// falling back to [`Self::logs`] when unavailable
+    pub async fn follow_logs(
+        token: &str,
+        deployment_id: &str,
+        deadline: Duration,
+    ) -> Result<FollowedLogs> {
+        match Self::subscribe_logs(token, deployment_id).await {
+            Ok(subscription) => {
+                Self::drain_logs(token, deployment_id, subscription, deadline).await
+            }
+            Err(err) => {
+                warn!("Unable to subscribe to deploy logs of {deployment_id}, querying instead: {err}");
+                Ok(FollowedLogs {
+                    logs: Self::logs(token, deployment_id).await?,
+                    truncated: false,
+                })
+            }
+        }
+    }
+
+    /// Railway replays existing logs first then keeps the subscription open, so stop once it
+    /// goes quiet with the deployment settled. A slow build still writing at `deadline` has its
+    /// logs marked as truncated
+    async fn drain_logs(
+        token: &str,
+        deployment_id: &str,
+        mut subscription: Subscription<DeploymentLogsEvent>,
+        deadline: Duration,
+    ) -> Result<FollowedLogs> {
+        let deadline = Instant::now() + deadline;
+        let mut logs = Vec::new();
+        let truncated = loop {
+            let idle = LOGS_IDLE.min(deadline.saturating_duration_since(Instant::now()));
+            match tokio::time::timeout(idle, subscription.next()).await {
+                Ok(Some(event)) => logs.extend(event?.logs()),
+                Ok(None) => break false,
+                Err(_) if Instant::now() >= deadline => break true,
+                Err(_) => {
+                    if Self::timeline(token, deployment_id)
+                        .await?
+                        .status()
+                        .is_terminal()
+                    {
+                        break false;
+                    }
+                }
+            }
+        };
+        let _ = subscription.close().await;
+        if truncated {
+            warn!("Logs of {deployment_id} were cut off before the deployment settled");
+        }
+        Ok(FollowedLogs { logs, truncated })
+    }
+
+    /// Deploys the same source again as a new deployment, returning its id
+    pub async fn redeploy(token: &str, deployment_id: &str) -> Result<String> {
+        let response: DeploymentRedeployResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": REDEPLOY,
+                "variables": {
+                    "id": deployment_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentRedeployDeployment {
+            id: String,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentRedeployResponse {
+            deployment_redeploy: DeploymentRedeployDeployment,
+        }
+
+        Ok(response.deployment_redeploy.id)
+    }
+
+    /// Restarts the running container of a deployment, keeping the same deployment
+    pub async fn restart(token: &str, deployment_id: &str) -> Result<()> {
+        let response: DeploymentRestartResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": RESTART,
+                "variables": {
+                    "id": deployment_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentRestartResponse {
+            deployment_restart: bool,
+        }
+
+        if response.deployment_restart {
+            Ok(())
+        } else {
+            Err(Error::Railway(vec![format!(
+                "deployment {deployment_id} was not restarted"
+            )]))
+        }
+    }
+
+    /// Stops a deployment that is still building or deploying
+    pub async fn cancel(token: &str, deployment_id: &str) -> Result<()> {
+        let response: DeploymentCancelResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": CANCEL,
+                "variables": {
+                    "id": deployment_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct DeploymentCancelResponse {
+            deployment_cancel: bool,
+        }
+
+        if response.deployment_cancel {
+            Ok(())
+        } else {
+            Err(Error::Railway(vec![format!(
+                "deployment {deployment_id} was not cancelled"
+            )]))
+        }
+    }
+
+    /// Waits for a single deployment to settle, streaming its status when possible
+    pub async fn terminal_status(token: &str, deployment_id: &str) -> Result<DeploymentStatus> {
+        let status = Self::timeline(token, deployment_id).await?.status().clone();
+        if status.is_terminal() {
+            return Ok(status);
+        }
+
+        match Self::follow_status(token, deployment_id, |_| {}).await {
+            Ok(status) => return Ok(status),
+            Err(err) => {
+                warn!("Unable to follow status of {deployment_id}, polling instead: {err}")
+            }
+        }
+
+        let mut interval = tokio::time::interval(STATUS_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let status = Self::timeline(token, deployment_id).await?.status().clone();
+            if status.is_terminal() {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Waits for a terminal status over a subscription, passing every status seen on to
+    /// `on_status`. The status is queried again once subscribed, as Railway doesn't replay one
+    /// reached before the subscription started
+    pub async fn follow_status(
+        token: &str,
+        deployment_id: &str,
+        on_status: impl Fn(&DeploymentStatus),
+    ) -> Result<DeploymentStatus> {
+        let subscription = Self::subscribe_status(token, deployment_id).await?;
+        let status = Self::timeline(token, deployment_id).await?.status().clone();
+        on_status(&status);
+        if status.is_terminal() {
+            let _ = subscription.close().await;
+            return Ok(status);
+        }
+        Self::wait_terminal(subscription, on_status).await
+    }
+
+    /// Follows the deployment status until it's terminal, passing every status on to `on_status`
+    pub async fn wait_terminal(
+        mut subscription: Subscription<DeploymentStatusEvent>,
+        on_status: impl Fn(&DeploymentStatus),
+    ) -> Result<DeploymentStatus> {
+        while let Some(event) = subscription.next().await {
+            let status = event?.status().clone();
+            on_status(&status);
+            if status.is_terminal() {
+                let _ = subscription.close().await;
+                return Ok(status);
+            }
+        }
+
+        Err(Error::Subscription(
+            "deployment status subscription ended before a terminal status".to_owned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::railway::{
+        fake::{self, accept_subscription, send},
+        Endpoints,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    fn timeline(status: &str) -> serde_json::Value {
+        serde_json::json!({
+            "deployment": {
+                "createdAt": "2024-01-01T00:00:00Z",
+                "status": status,
+                "statusUpdatedAt": null,
+            },
+            "deploymentEvents": { "edges": [] },
+        })
+    }
+
+    /// Reports `statuses` in turn, the last one from then on
+    async fn serve_timeline(statuses: &'static [&'static str]) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let url = fake::serve_graphql(move |operation| {
+            assert_eq!(operation, "deploymentEvents");
+            let call = counter.fetch_add(1, Ordering::SeqCst);
+            timeline(statuses[call.min(statuses.len() - 1)])
+        })
+        .await;
+        (url, calls)
+    }
+
+    #[tokio::test]
+    async fn terminal_status_follows_the_subscription() {
+        let (http, calls) = serve_timeline(&["BUILDING"]).await;
+        let ws = fake::serve_ws(|mut socket| async move {
+            let subscribe = accept_subscription(&mut socket).await;
+            assert_eq!(subscribe["payload"]["variables"]["id"], "d1");
+            for status in ["DEPLOYING", "SUCCESS"] {
+                send(
+                    &mut socket,
+                    serde_json::json!({
+                        "id": subscribe["id"],
+                        "type": "next",
+                        "payload": { "data": { "deployment": { "id": "d1", "status": status } } },
+                    }),
+                )
+                .await;
+            }
+            std::future::pending::<()>().await;
+        })
+        .await;
+
+        let status = Railway::scoped(
+            Endpoints { http, ws },
+            Deployment::terminal_status("token", "d1"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, DeploymentStatus::Success);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn terminal_status_polls_when_the_subscription_fails() {
+        let (http, calls) = serve_timeline(&["BUILDING", "BUILDING", "FAILED"]).await;
+        let ws = fake::serve_ws(|mut socket| async move {
+            let subscribe = accept_subscription(&mut socket).await;
+            send(
+                &mut socket,
+                serde_json::json!({
+                    "id": subscribe["id"],
+                    "type": "error",
+                    "payload": [{ "message": "subscriptions are unavailable" }],
+                }),
+            )
+            .await;
+        })
+        .await;
+
+        let status = Railway::scoped(
+            Endpoints { http, ws },
+            Deployment::terminal_status("token", "d1"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, DeploymentStatus::Failed);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
 }
@@ -0,0 +1,66 @@
+use crate::{Error, Railway, Result};
+use derive_get::Getters;
+use serde::{Deserialize, Serialize};
+
+const CREATE: &str = include_str!("../graphql/environment_create.gql");
+const DELETE: &str = include_str!("../graphql/environment_delete.gql");
+
+/// Isolated set of service instances and variables within a project, like `production`
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct Environment {
+    id: String,
+    name: String,
+}
+
+impl Environment {
+    /// Creates an empty environment, services are added to it as they are created
+    pub async fn create(token: &str, project_id: &str, name: &str) -> Result<Self> {
+        let response: EnvironmentCreateResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": CREATE,
+                "variables": {
+                    "projectId": project_id,
+                    "name": name,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct EnvironmentCreateResponse {
+            environment_create: Environment,
+        }
+
+        Ok(response.environment_create)
+    }
+
+    /// Deletes the environment along with its service instances
+    pub async fn delete(token: &str, environment_id: &str) -> Result<()> {
+        let response: EnvironmentDeleteResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": DELETE,
+                "variables": {
+                    "id": environment_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct EnvironmentDeleteResponse {
+            environment_delete: bool,
+        }
+
+        if response.environment_delete {
+            Ok(())
+        } else {
+            Err(Error::Railway(vec![format!(
+                "environment {environment_id} was not deleted"
+            )]))
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Local stand-ins for Railway's HTTP and websocket APIs
+
+use super::operation_name;
+use axum::{routing::post, Json, Router};
+use futures_util::{SinkExt, StreamExt};
+use std::{future::Future, net::SocketAddr, sync::Arc};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+
+pub(crate) type Socket = WebSocketStream<TcpStream>;
+
+/// Answers every GraphQL request with `{"data": respond(operation)}`
+pub(crate) async fn serve_graphql(
+    respond: impl Fn(&str) -> serde_json::Value + Send + Sync + 'static,
+) -> String {
+    let respond = Arc::new(respond);
+    let app = Router::new().route(
+        "/",
+        post(move |Json(body): Json<serde_json::Value>| {
+            let respond = Arc::clone(&respond);
+            async move { Json(serde_json::json!({ "data": respond(operation_name(&body)) })) }
+        }),
+    );
+
+    let address = SocketAddr::from(([127, 0, 0, 1], 0));
+    let server = axum::Server::bind(&address).serve(app.into_make_service());
+    let url = format!("http://{}/", server.local_addr());
+    tokio::spawn(server);
+    url
+}
+
+/// Hands every websocket connection to its own `session`
+pub(crate) async fn serve_ws<F, Fut>(session: F) -> String
+where
+    F: Fn(Socket) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            if let Ok(socket) = tokio_tungstenite::accept_async(stream).await {
+                tokio::spawn(session(socket));
+            }
+        }
+    });
+    url
+}
+
+/// A websocket URL nothing listens on
+pub(crate) async fn refused_ws() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("ws://{}", listener.local_addr().unwrap())
+}
+
+/// Next graphql-ws message from the client, which must be of type `kind`
+pub(crate) async fn expect(socket: &mut Socket, kind: &str) -> serde_json::Value {
+    loop {
+        match socket.next().await {
+            Some(Ok(Message::Text(text))) => {
+                let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+                assert_eq!(message["type"], kind, "{message}");
+                return message;
+            }
+            Some(Ok(_)) => continue,
+            other => panic!("expected {kind}, got {other:?}"),
+        }
+    }
+}
+
+pub(crate) async fn send(socket: &mut Socket, message: serde_json::Value) {
+    socket
+        .send(Message::Text(message.to_string()))
+        .await
+        .unwrap();
+}
+
+/// Acknowledges the connection and waits for the client to subscribe
+pub(crate) async fn accept_subscription(socket: &mut Socket) -> serde_json::Value {
+    expect(socket, "connection_init").await;
+    send(socket, serde_json::json!({ "type": "connection_ack" })).await;
+    expect(socket, "subscribe").await
+}
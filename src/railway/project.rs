@@ -1,11 +1,145 @@
-use crate::{Error, Railway, Result};
+use crate::{railway::environment::Environment, Error, Railway, Result};
+use chrono::{DateTime, Utc};
+use derive_get::Getters;
 use serde::{Deserialize, Serialize};
 
+const CREATE: &str = include_str!("../graphql/project_create.gql");
 const DELETE: &str = include_str!("../graphql/project_delete.gql");
+const GET: &str = include_str!("../graphql/project_get.gql");
+const LIST: &str = include_str!("../graphql/project_list.gql");
 
-pub struct Project;
+#[derive(Getters, Clone, Debug)]
+pub struct Project {
+    id: String,
+    name: String,
+    description: Option<String>,
+    #[copy]
+    created_at: DateTime<Utc>,
+    environments: Vec<Environment>,
+}
+
+/// Shape of a project as every project query returns it
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ProjectNode {
+    id: String,
+    name: String,
+    description: Option<String>,
+    created_at: DateTime<Utc>,
+    environments: ProjectNodeEnvironments,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ProjectNodeEnvironments {
+    edges: Vec<ProjectNodeEnvironmentsEdge>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ProjectNodeEnvironmentsEdge {
+    node: Environment,
+}
+
+impl From<ProjectNode> for Project {
+    fn from(node: ProjectNode) -> Self {
+        Self {
+            id: node.id,
+            name: node.name,
+            description: node.description,
+            created_at: node.created_at,
+            environments: node
+                .environments
+                .edges
+                .into_iter()
+                .map(|e| e.node)
+                .collect(),
+        }
+    }
+}
 
 impl Project {
+    /// Creates an empty project, Railway gives it a `production` environment
+    pub async fn create(token: &str, name: &str, description: Option<&str>) -> Result<Self> {
+        let response: ProjectCreateResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": CREATE,
+                "variables": {
+                    "name": name,
+                    "description": description,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ProjectCreateResponse {
+            project_create: ProjectNode,
+        }
+
+        Ok(response.project_create.into())
+    }
+
+    pub async fn get(token: &str, project_id: &str) -> Result<Self> {
+        let response: ProjectResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": GET,
+                "variables": {
+                    "id": project_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ProjectResponse {
+            project: ProjectNode,
+        }
+
+        Ok(response.project.into())
+    }
+
+    /// Every project the token has access to
+    pub async fn list(token: &str) -> Result<Vec<Self>> {
+        let response: ProjectsResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": LIST,
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ProjectsEdge {
+            node: ProjectNode,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct Projects {
+            edges: Vec<ProjectsEdge>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ProjectsResponse {
+            projects: Projects,
+        }
+
+        Ok(response
+            .projects
+            .edges
+            .into_iter()
+            .map(|e| e.node.into())
+            .collect())
+    }
+
+    /// Deletes the project with everything deployed in it
     pub async fn delete(token: &str, project_id: &str) -> Result<()> {
         let response: ProjectDeleteResponse = Railway::query(
             token,
@@ -1,17 +1,127 @@
-use crate::{Error, Railway, Result};
+use super::within;
+use crate::{Deployment, DeploymentStatus, Error, Railway, Result};
 use derive_get::Getters;
+use futures_util::future::try_join_all;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+use tracing::{info_span, warn, Instrument};
 
+const CREATE: &str = include_str!("../graphql/service_create.gql");
+const DELETE: &str = include_str!("../graphql/service_delete.gql");
+const DOMAIN_CREATE: &str = include_str!("../graphql/service_domain_create.gql");
+const INSTANCE_UPDATE: &str = include_str!("../graphql/service_instance_update.gql");
+const LATEST_DEPLOYMENT: &str = include_str!("../graphql/service_instance_latest_deployment.gql");
 const LIST: &str = include_str!("../graphql/service_list.gql");
+const REDEPLOY: &str = include_str!("../graphql/service_instance_redeploy.gql");
+const TCP_PROXIES: &str = include_str!("../graphql/tcp_proxies.gql");
+const UPDATE: &str = include_str!("../graphql/service_update.gql");
+const VARIABLE_UPSERT: &str = include_str!("../graphql/variable_upsert.gql");
+const VARIABLES: &str = include_str!("../graphql/variables.gql");
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DomainKind {
+    /// Generated by Railway under `up.railway.app`
+    Service,
+    Custom,
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+pub struct Domain {
+    domain: String,
+    #[copy]
+    kind: DomainKind,
+    #[copy]
+    target_port: Option<u16>,
+}
+
+/// Public TCP endpoint forwarding to a port inside the service
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpProxy {
+    domain: String,
+    #[copy]
+    proxy_port: u16,
+    #[copy]
+    application_port: u16,
+}
+
+impl TcpProxy {
+    /// `host:port` to connect to from outside Railway
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.domain, self.proxy_port)
+    }
+}
+
+/// How a service instance can be reached from outside Railway
+#[derive(Getters, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Networking {
+    domains: Vec<Domain>,
+    tcp_proxies: Vec<TcpProxy>,
+}
 
 #[derive(Getters, Clone, Debug)]
 pub struct ServiceInstance {
+    environment_id: String,
     healthcheck_path: Option<String>,
     healthcheck_timeout: Option<u64>,
+    /// Docker image the service was deployed from, `None` for repos
+    source_image: Option<String>,
     static_url: Option<String>,
-    status: Option<String>,
+    status: Option<DeploymentStatus>,
     deployment_id: Option<String>,
+    /// TCP proxies are only filled in by [`Service::list_with_networking`]
+    networking: Networking,
+}
+
+/// State of a service's latest deployment, terminal once the wait for builds is over
+#[derive(Getters, Clone, Debug)]
+pub struct BuildResult {
+    service_id: String,
+    service_name: String,
+    deployment_id: Option<String>,
+    status: DeploymentStatus,
+}
+
+/// Where a service deploys from
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum ServiceSource {
+    /// Docker image, like `postgres:16`
+    Image(String),
+    /// GitHub repository, like `railwayapp/starters`
+    Repo(String),
+}
+
+/// Service settings shared by every environment, unset fields are left as they are
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+}
+
+/// Settings of a service in one environment, unset fields are left as they are
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceInstanceUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<ServiceSource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_directory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub healthcheck_path: Option<String>,
+    /// In seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub healthcheck_timeout: Option<u64>,
 }
 
 #[derive(Getters, Clone, Debug)]
@@ -22,34 +132,137 @@ pub struct Service {
 }
 
 impl Service {
-    pub async fn wait_for_all_builds(token: &str, project_id: &str) -> Result<()> {
+    /// Waits for every deployment to reach a terminal state, failing with
+    /// [`crate::Error::RailwayTimeout`] after `deadline`. Each status a deployment goes through
+    /// is passed on to `on_change` once
+    pub async fn wait_for_all_builds(
+        token: &str,
+        project_id: &str,
+        deadline: Duration,
+        on_change: impl Fn(&BuildResult) + Sync,
+    ) -> Result<Vec<BuildResult>> {
+        // Polling taking over from the subscriptions sees some statuses again
+        let seen = Mutex::new(HashMap::new());
+        let changed = |build: &BuildResult| {
+            let key = build
+                .deployment_id
+                .clone()
+                .unwrap_or_else(|| build.service_id.clone());
+            let mut seen = seen.lock().unwrap_or_else(PoisonError::into_inner);
+            if seen.get(&key) != Some(&build.status) {
+                seen.insert(key, build.status.clone());
+                on_change(build);
+            }
+        };
+
+        within(deadline, async {
+            match Self::watch_builds(token, project_id, &changed).await {
+                Ok(results) => Ok(results),
+                Err(err) => {
+                    warn!("Unable to follow builds of {project_id} live, polling instead: {err}");
+                    Self::poll_builds(token, project_id, &changed).await
+                }
+            }
+        })
+        .await
+    }
+
+    /// Streams each deployment's status instead of polling the whole project
+    async fn watch_builds(
+        token: &str,
+        project_id: &str,
+        on_change: &(dyn Fn(&BuildResult) + Sync),
+    ) -> Result<Vec<BuildResult>> {
         let mut interval = tokio::time::interval(Duration::from_secs(10));
-        'outer: loop {
+        let services = loop {
             interval.tick().await;
 
             let services = Self::list(token, project_id).await?;
-            for service in services {
-                if service.instances().is_empty() {
-                    continue;
-                }
+            if services
+                .iter()
+                .flat_map(|s| &s.instances)
+                .all(|i| i.deployment_id.is_some())
+            {
+                break services;
+            }
+        };
 
-                for instance in service.instances() {
-                    let status = instance.status().as_deref();
-                    if status.is_none()
-                        || status == Some("BUILDING")
-                        || status == Some("WAITING")
-                        || status == Some("INITIALIZING")
-                        || status == Some("QUEUED")
-                    {
-                        continue 'outer;
+        let mut builds = Vec::new();
+        for service in services {
+            for instance in service.instances {
+                let service_id = service.id.clone();
+                let service_name = service.name.clone();
+                let deployment_id = instance.deployment_id.unwrap_or_default();
+                let span = info_span!(
+                    "deployment",
+                    service = %service_name,
+                    deployment.id = %deployment_id,
+                );
+                builds.push(
+                    async move {
+                        let build = |status: &DeploymentStatus| BuildResult {
+                            service_id: service_id.clone(),
+                            service_name: service_name.clone(),
+                            deployment_id: Some(deployment_id.clone()),
+                            status: status.clone(),
+                        };
+                        if let Some(status) = &instance.status {
+                            on_change(&build(status));
+                        }
+                        let status = match instance.status {
+                            Some(status) if status.is_terminal() => status,
+                            _ => {
+                                Deployment::follow_status(token, &deployment_id, |status| {
+                                    on_change(&build(status))
+                                })
+                                .await?
+                            }
+                        };
+                        Ok::<_, Error>(build(&status))
                     }
+                    .instrument(span),
+                );
+            }
+        }
+
+        try_join_all(builds).await
+    }
+
+    async fn poll_builds(
+        token: &str,
+        project_id: &str,
+        on_change: &(dyn Fn(&BuildResult) + Sync),
+    ) -> Result<Vec<BuildResult>> {
+        let mut interval = tokio::time::interval(Duration::from_secs(10));
+        loop {
+            interval.tick().await;
+
+            let mut results = Vec::new();
+            let mut settled = true;
+            for service in Self::list(token, project_id).await? {
+                for instance in service.instances {
+                    let Some(status) = instance.status else {
+                        settled = false;
+                        continue;
+                    };
+                    let build = BuildResult {
+                        service_id: service.id.clone(),
+                        service_name: service.name.clone(),
+                        deployment_id: instance.deployment_id,
+                        status,
+                    };
+                    on_change(&build);
+                    settled &= build.status.is_terminal();
+                    results.push(build);
                 }
             }
-            break;
+            if settled {
+                return Ok(results);
+            }
         }
-        Ok(())
     }
 
+    /// Services of a project with an instance per environment, without TCP proxies
     pub async fn list(token: &str, project_id: &str) -> Result<Vec<Self>> {
         let response: ServiceList = Railway::query(
             token,
@@ -67,14 +280,39 @@ impl Service {
         struct ServiceListProjectServiceEdgeNodeServiceInstancesEdgeNodeLatestDeployment {
             id: String,
             static_url: Option<String>,
-            status: String,
+            status: DeploymentStatus,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceListProjectServiceEdgeNodeServiceInstancesEdgeNodeDomainsDomain {
+            domain: String,
+            target_port: Option<u16>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceListProjectServiceEdgeNodeServiceInstancesEdgeNodeDomains {
+            service_domains:
+                Vec<ServiceListProjectServiceEdgeNodeServiceInstancesEdgeNodeDomainsDomain>,
+            custom_domains:
+                Vec<ServiceListProjectServiceEdgeNodeServiceInstancesEdgeNodeDomainsDomain>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceListProjectServiceEdgeNodeServiceInstancesEdgeNodeSource {
+            image: Option<String>,
         }
 
         #[derive(Serialize, Deserialize, Debug)]
         #[serde(rename_all = "camelCase")]
         struct ServiceListProjectServiceEdgeNodeServiceInstancesEdgeNode {
+            environment_id: String,
             healthcheck_path: Option<String>,
             healthcheck_timeout: Option<u64>,
+            source: Option<ServiceListProjectServiceEdgeNodeServiceInstancesEdgeNodeSource>,
+            domains: ServiceListProjectServiceEdgeNodeServiceInstancesEdgeNodeDomains,
             latest_deployment:
                 Option<ServiceListProjectServiceEdgeNodeServiceInstancesEdgeNodeLatestDeployment>,
         }
@@ -134,9 +372,31 @@ impl Service {
                     .edges
                     .into_iter()
                     .map(|i| {
-                        Ok::<_, Error>(ServiceInstance {
+                        let domains = i
+                            .node
+                            .domains
+                            .service_domains
+                            .into_iter()
+                            .map(|d| (d, DomainKind::Service))
+                            .chain(
+                                i.node
+                                    .domains
+                                    .custom_domains
+                                    .into_iter()
+                                    .map(|d| (d, DomainKind::Custom)),
+                            )
+                            .map(|(d, kind)| Domain {
+                                domain: d.domain,
+                                kind,
+                                target_port: d.target_port,
+                            })
+                            .collect();
+
+                        ServiceInstance {
+                            environment_id: i.node.environment_id,
                             healthcheck_path: i.node.healthcheck_path,
                             healthcheck_timeout: i.node.healthcheck_timeout,
+                            source_image: i.node.source.and_then(|s| s.image),
                             static_url: i
                                 .node
                                 .latest_deployment
@@ -144,11 +404,543 @@ impl Service {
                                 .and_then(|d| d.static_url.clone()),
                             status: i.node.latest_deployment.as_ref().map(|d| d.status.clone()),
                             deployment_id: i.node.latest_deployment.as_ref().map(|d| d.id.clone()),
-                        })
+                            networking: Networking {
+                                domains,
+                                tcp_proxies: Vec::new(),
+                            },
+                        }
                     })
-                    .collect::<Result<Vec<_>>>()?,
+                    .collect(),
             });
         }
         Ok(views)
     }
+
+    /// Like [`Self::list`], also querying every instance's TCP proxies
+    pub async fn list_with_networking(token: &str, project_id: &str) -> Result<Vec<Self>> {
+        let mut services = Self::list(token, project_id).await?;
+        for service in &mut services {
+            let proxies = try_join_all(
+                service
+                    .instances
+                    .iter()
+                    .map(|i| Self::tcp_proxies(token, &service.id, &i.environment_id)),
+            )
+            .await?;
+            for (instance, proxies) in service.instances.iter_mut().zip(proxies) {
+                instance.networking.tcp_proxies = proxies;
+            }
+        }
+        Ok(services)
+    }
+
+    /// Public TCP endpoints of a service instance
+    pub async fn tcp_proxies(
+        token: &str,
+        service_id: &str,
+        environment_id: &str,
+    ) -> Result<Vec<TcpProxy>> {
+        let response: TcpProxiesResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": TCP_PROXIES,
+                "variables": {
+                    "environmentId": environment_id,
+                    "serviceId": service_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct TcpProxiesResponse {
+            tcp_proxies: Vec<TcpProxy>,
+        }
+
+        Ok(response.tcp_proxies)
+    }
+
+    /// Id of the deployment currently serving a service instance
+    pub async fn latest_deployment_id(
+        token: &str,
+        service_id: &str,
+        environment_id: &str,
+    ) -> Result<Option<String>> {
+        let response: ServiceInstanceResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": LATEST_DEPLOYMENT,
+                "variables": {
+                    "environmentId": environment_id,
+                    "serviceId": service_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceInstanceLatestDeployment {
+            id: String,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceInstance {
+            latest_deployment: Option<ServiceInstanceLatestDeployment>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceInstanceResponse {
+            service_instance: ServiceInstance,
+        }
+
+        Ok(response.service_instance.latest_deployment.map(|d| d.id))
+    }
+
+    /// Redeploys a service instance the way the dashboard does, returning the new deployment id
+    /// once Railway created it. Fails with [`crate::Error::RailwayTimeout`] after `timeout`
+    pub async fn redeploy(
+        token: &str,
+        service_id: &str,
+        environment_id: &str,
+        timeout: Duration,
+    ) -> Result<String> {
+        within(
+            timeout,
+            Self::start_redeploy(token, service_id, environment_id),
+        )
+        .await
+    }
+
+    async fn start_redeploy(token: &str, service_id: &str, environment_id: &str) -> Result<String> {
+        let previous = Self::latest_deployment_id(token, service_id, environment_id).await?;
+
+        let response: ServiceInstanceRedeployResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": REDEPLOY,
+                "variables": {
+                    "environmentId": environment_id,
+                    "serviceId": service_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceInstanceRedeployResponse {
+            service_instance_redeploy: bool,
+        }
+
+        if !response.service_instance_redeploy {
+            return Err(Error::Railway(vec![format!(
+                "service {service_id} was not redeployed"
+            )]));
+        }
+
+        let mut interval = tokio::time::interval(Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+            let latest = Self::latest_deployment_id(token, service_id, environment_id).await?;
+            if let Some(latest) = latest.filter(|latest| Some(latest) != previous.as_ref()) {
+                return Ok(latest);
+            }
+        }
+    }
+
+    /// Resolved variables of a service, including the ones the template generated
+    pub async fn variables(
+        token: &str,
+        project_id: &str,
+        environment_id: &str,
+        service_id: &str,
+    ) -> Result<HashMap<String, String>> {
+        let response: VariablesResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": VARIABLES,
+                "variables": {
+                    "projectId": project_id,
+                    "environmentId": environment_id,
+                    "serviceId": service_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct VariablesResponse {
+            variables: HashMap<String, String>,
+        }
+
+        Ok(response.variables)
+    }
+
+    /// Creates a service in every environment of the project, deploying `source` if set
+    pub async fn create(
+        token: &str,
+        project_id: &str,
+        name: &str,
+        source: Option<&ServiceSource>,
+    ) -> Result<Self> {
+        let response: ServiceCreateResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": CREATE,
+                "variables": {
+                    "projectId": project_id,
+                    "name": name,
+                    "source": source,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceCreateService {
+            id: String,
+            name: String,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceCreateResponse {
+            service_create: ServiceCreateService,
+        }
+
+        // Instances are only listed once their first deployment exists
+        Ok(Service {
+            id: response.service_create.id,
+            name: response.service_create.name,
+            instances: Vec::new(),
+        })
+    }
+
+    /// Renames a service or changes its icon
+    pub async fn update(token: &str, service_id: &str, update: &ServiceUpdate) -> Result<()> {
+        Railway::query::<serde_json::Value>(
+            token,
+            serde_json::json!({
+                "query": UPDATE,
+                "variables": {
+                    "id": service_id,
+                    "input": update,
+                }
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Changes take effect on the next deployment of the instance
+    pub async fn update_instance(
+        token: &str,
+        service_id: &str,
+        environment_id: &str,
+        update: &ServiceInstanceUpdate,
+    ) -> Result<()> {
+        let response: ServiceInstanceUpdateResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": INSTANCE_UPDATE,
+                "variables": {
+                    "environmentId": environment_id,
+                    "serviceId": service_id,
+                    "input": update,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceInstanceUpdateResponse {
+            service_instance_update: bool,
+        }
+
+        if response.service_instance_update {
+            Ok(())
+        } else {
+            Err(Error::Railway(vec![format!(
+                "service {service_id} was not updated"
+            )]))
+        }
+    }
+
+    /// Deletes the service from every environment, along with its deployments
+    pub async fn delete(token: &str, service_id: &str) -> Result<()> {
+        let response: ServiceDeleteResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": DELETE,
+                "variables": {
+                    "id": service_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceDeleteResponse {
+            service_delete: bool,
+        }
+
+        if response.service_delete {
+            Ok(())
+        } else {
+            Err(Error::Railway(vec![format!(
+                "service {service_id} was not deleted"
+            )]))
+        }
+    }
+
+    /// Sets a variable of a service, or one shared by the whole environment when
+    /// `service_id` is `None`
+    pub async fn upsert_variable(
+        token: &str,
+        project_id: &str,
+        environment_id: &str,
+        service_id: Option<&str>,
+        name: &str,
+        value: &str,
+    ) -> Result<()> {
+        let response: VariableUpsertResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": VARIABLE_UPSERT,
+                "variables": {
+                    "projectId": project_id,
+                    "environmentId": environment_id,
+                    "serviceId": service_id,
+                    "name": name,
+                    "value": value,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct VariableUpsertResponse {
+            variable_upsert: bool,
+        }
+
+        if response.variable_upsert {
+            Ok(())
+        } else {
+            Err(Error::Railway(vec![format!("variable {name} was not set")]))
+        }
+    }
+
+    /// Generates an `up.railway.app` domain routed to `target_port`, or to the port the
+    /// service listens on when `None`
+    pub async fn create_domain(
+        token: &str,
+        service_id: &str,
+        environment_id: &str,
+        target_port: Option<u16>,
+    ) -> Result<Domain> {
+        let response: ServiceDomainCreateResponse = Railway::query(
+            token,
+            serde_json::json!({
+                "query": DOMAIN_CREATE,
+                "variables": {
+                    "environmentId": environment_id,
+                    "serviceId": service_id,
+                    "targetPort": target_port,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceDomainCreateDomain {
+            domain: String,
+            target_port: Option<u16>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct ServiceDomainCreateResponse {
+            service_domain_create: ServiceDomainCreateDomain,
+        }
+
+        Ok(Domain {
+            domain: response.service_domain_create.domain,
+            kind: DomainKind::Service,
+            target_port: response.service_domain_create.target_port,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::railway::{
+        fake::{self, accept_subscription, send},
+        Endpoints,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    fn services(status: &str) -> serde_json::Value {
+        serde_json::json!({
+            "project": {
+                "services": {
+                    "edges": [{
+                        "node": {
+                            "id": "s1",
+                            "name": "web",
+                            "serviceInstances": {
+                                "edges": [{
+                                    "node": {
+                                        "environmentId": "e1",
+                                        "healthcheckPath": null,
+                                        "healthcheckTimeout": null,
+                                        "source": null,
+                                        "domains": { "serviceDomains": [], "customDomains": [] },
+                                        "latestDeployment": {
+                                            "id": "d1",
+                                            "staticUrl": null,
+                                            "status": status,
+                                        },
+                                    },
+                                }],
+                            },
+                        },
+                    }],
+                },
+            },
+        })
+    }
+
+    async fn wait(http: String, ws: String) -> (Vec<BuildResult>, Vec<DeploymentStatus>) {
+        let changes = Mutex::new(Vec::new());
+        let builds = Railway::scoped(
+            Endpoints { http, ws },
+            Service::wait_for_all_builds("token", "p1", Duration::from_secs(3600), |build| {
+                changes.lock().unwrap().push(build.status().clone())
+            }),
+        )
+        .await
+        .unwrap();
+        (builds, changes.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn builds_are_followed_over_subscriptions() {
+        let http = fake::serve_graphql(|operation| match operation {
+            "project" => services("BUILDING"),
+            "deploymentEvents" => serde_json::json!({
+                "deployment": {
+                    "createdAt": "2024-01-01T00:00:00Z",
+                    "status": "BUILDING",
+                    "statusUpdatedAt": null,
+                },
+                "deploymentEvents": { "edges": [] },
+            }),
+            other => panic!("unexpected {other} query"),
+        })
+        .await;
+        let ws = fake::serve_ws(|mut socket| async move {
+            let subscribe = accept_subscription(&mut socket).await;
+            for status in ["DEPLOYING", "SUCCESS"] {
+                send(
+                    &mut socket,
+                    serde_json::json!({
+                        "id": subscribe["id"],
+                        "type": "next",
+                        "payload": { "data": { "deployment": { "id": "d1", "status": status } } },
+                    }),
+                )
+                .await;
+            }
+            std::future::pending::<()>().await;
+        })
+        .await;
+
+        let (builds, changes) = wait(http, ws).await;
+        assert_eq!(builds.len(), 1);
+        assert_eq!(builds[0].deployment_id().as_deref(), Some("d1"));
+        assert_eq!(builds[0].status(), &DeploymentStatus::Success);
+        assert_eq!(
+            changes,
+            [
+                DeploymentStatus::Building,
+                DeploymentStatus::Deploying,
+                DeploymentStatus::Success,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn builds_are_polled_when_subscriptions_fail() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let http = fake::serve_graphql(move |operation| {
+            assert_eq!(operation, "project");
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => services("BUILDING"),
+                _ => services("SUCCESS"),
+            }
+        })
+        .await;
+
+        let (builds, changes) = wait(http, fake::refused_ws().await).await;
+        assert_eq!(builds[0].status(), &DeploymentStatus::Success);
+        assert_eq!(
+            changes,
+            [DeploymentStatus::Building, DeploymentStatus::Success]
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    async fn serve_redeploy(redeployed_after: usize) -> String {
+        let polls = Arc::new(AtomicUsize::new(0));
+        fake::serve_graphql(move |operation| match operation {
+            "serviceInstanceRedeploy" => serde_json::json!({ "serviceInstanceRedeploy": true }),
+            "serviceInstance" => {
+                let poll = polls.fetch_add(1, Ordering::SeqCst);
+                let id = if poll > redeployed_after { "d2" } else { "d1" };
+                serde_json::json!({ "serviceInstance": { "latestDeployment": { "id": id } } })
+            }
+            other => panic!("unexpected {other} query"),
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn redeploys_return_the_new_deployment() {
+        let endpoints = Endpoints {
+            http: serve_redeploy(0).await,
+            ws: fake::refused_ws().await,
+        };
+        let redeploy = Service::redeploy("token", "s1", "e1", Duration::from_secs(60));
+        let deployment_id = Railway::scoped(endpoints, redeploy).await.unwrap();
+        assert_eq!(deployment_id, "d2");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn redeploys_time_out_without_a_new_deployment() {
+        let endpoints = Endpoints {
+            http: serve_redeploy(usize::MAX).await,
+            ws: fake::refused_ws().await,
+        };
+        let redeploy = Service::redeploy("token", "s1", "e1", Duration::from_secs(60));
+        let result = Railway::scoped(endpoints, redeploy).await;
+        assert!(
+            matches!(result, Err(Error::RailwayTimeout(timeout)) if timeout == Duration::from_secs(60)),
+            "{result:?}"
+        );
+    }
 }
@@ -0,0 +1,327 @@
+use super::{Endpoints, RailwayError};
+use crate::{Error, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{marker::PhantomData, time::Duration};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use tracing::trace;
+
+/// Sub-protocol spoken by Railway, see https://github.com/enisdenjo/graphql-ws/blob/master/PROTOCOL.md
+const PROTOCOL: &str = "graphql-transport-ws";
+const SUBSCRIPTION_ID: &str = "1";
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    ConnectionAck,
+    Ping,
+    Pong,
+    Next { payload: NextPayload },
+    Error { payload: Vec<RailwayError> },
+    Complete,
+}
+
+#[derive(Deserialize, Debug)]
+struct NextPayload {
+    #[serde(default)]
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    errors: Vec<RailwayError>,
+}
+
+/// A single GraphQL subscription over its own websocket connection
+pub struct Subscription<T> {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    done: bool,
+    _data: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Subscription<T> {
+    /// Subscribes against Railway's websocket API
+    pub async fn start(token: &str, query: serde_json::Value) -> Result<Self> {
+        Self::start_at(&Endpoints::current().ws, token, query).await
+    }
+
+    /// Subscribes against any graphql-ws server, Railway's or a local stand-in
+    pub async fn start_at(url: &str, token: &str, query: serde_json::Value) -> Result<Self> {
+        trace!("Subscribing: {query:#?}");
+
+        let mut request = url.into_client_request()?;
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", HeaderValue::from_static(PROTOCOL));
+        let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+
+        let mut subscription = Self {
+            socket,
+            done: false,
+            _data: PhantomData,
+        };
+
+        subscription
+            .send(serde_json::json!({
+                "type": "connection_init",
+                "payload": {
+                    "Authorization": format!("Bearer {token}"),
+                },
+            }))
+            .await?;
+
+        tokio::time::timeout(ACK_TIMEOUT, subscription.wait_ack())
+            .await
+            .map_err(|_| Error::Subscription("no connection_ack from server".to_owned()))??;
+
+        subscription
+            .send(serde_json::json!({
+                "id": SUBSCRIPTION_ID,
+                "type": "subscribe",
+                "payload": query,
+            }))
+            .await?;
+
+        Ok(subscription)
+    }
+
+    async fn wait_ack(&mut self) -> Result<()> {
+        loop {
+            match self.receive().await? {
+                Some(ServerMessage::ConnectionAck) => return Ok(()),
+                Some(ServerMessage::Ping) => self.pong().await?,
+                Some(message) => {
+                    return Err(Error::Subscription(format!(
+                        "expected connection_ack, got {message:?}"
+                    )))
+                }
+                None => {
+                    return Err(Error::Subscription(
+                        "connection closed before connection_ack".to_owned(),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Next event of the subscription, `None` once the server completes it
+    pub async fn next(&mut self) -> Option<Result<T>> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            let message = match self.receive().await {
+                Ok(Some(message)) => message,
+                Ok(None) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            };
+
+            match message {
+                ServerMessage::Next { payload } => {
+                    if !payload.errors.is_empty() {
+                        return Some(Err(Error::Railway(
+                            payload.errors.into_iter().map(|e| e.message).collect(),
+                        )));
+                    }
+
+                    let Some(data) = payload.data else {
+                        return Some(Err(Error::RailwayDataMissing(
+                            "no data in subscription event",
+                        )));
+                    };
+                    return Some(
+                        T::deserialize(&data).map_err(|err| Error::JsonWithMetadata(err, data)),
+                    );
+                }
+                ServerMessage::Error { payload } => {
+                    self.done = true;
+                    return Some(Err(Error::Railway(
+                        payload.into_iter().map(|e| e.message).collect(),
+                    )));
+                }
+                ServerMessage::Complete => {
+                    self.done = true;
+                    return None;
+                }
+                ServerMessage::Ping => {
+                    if let Err(err) = self.pong().await {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                }
+                ServerMessage::Pong | ServerMessage::ConnectionAck => {}
+            }
+        }
+    }
+
+    /// Tells the server we're done and closes the connection
+    pub async fn close(mut self) -> Result<()> {
+        if !self.done {
+            self.send(serde_json::json!({ "id": SUBSCRIPTION_ID, "type": "complete" }))
+                .await?;
+        }
+        self.socket.close(None).await?;
+        Ok(())
+    }
+
+    async fn pong(&mut self) -> Result<()> {
+        self.send(serde_json::json!({ "type": "pong" })).await
+    }
+
+    async fn send(&mut self, message: serde_json::Value) -> Result<()> {
+        self.socket.send(Message::Text(message.to_string())).await?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Option<ServerMessage>> {
+        while let Some(message) = self.socket.next().await {
+            let text = match message? {
+                Message::Text(text) => text,
+                Message::Binary(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                Message::Ping(payload) => {
+                    self.socket.send(Message::Pong(payload)).await?;
+                    continue;
+                }
+                Message::Close(_) => return Ok(None),
+                Message::Pong(_) | Message::Frame(_) => continue,
+            };
+
+            trace!("Subscription message: {text}");
+            let message = serde_json::from_str(&text).map_err(|err| {
+                Error::JsonWithMetadata(err, serde_json::Value::String(text.clone()))
+            })?;
+            return Ok(Some(message));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::railway::fake::{self, accept_subscription, expect, send};
+
+    fn query() -> serde_json::Value {
+        serde_json::json!({ "query": "subscription ticks { tick }" })
+    }
+
+    #[tokio::test]
+    async fn streams_events_until_complete() {
+        let url = fake::serve_ws(|mut socket| async move {
+            let init = expect(&mut socket, "connection_init").await;
+            assert_eq!(init["payload"]["Authorization"], "Bearer token");
+            send(&mut socket, serde_json::json!({ "type": "connection_ack" })).await;
+
+            let subscribe = expect(&mut socket, "subscribe").await;
+            assert_eq!(subscribe["id"], SUBSCRIPTION_ID);
+            assert_eq!(subscribe["payload"], query());
+            for tick in 1..=2 {
+                send(
+                    &mut socket,
+                    serde_json::json!({
+                        "id": SUBSCRIPTION_ID,
+                        "type": "next",
+                        "payload": { "data": { "tick": tick } },
+                    }),
+                )
+                .await;
+            }
+            send(
+                &mut socket,
+                serde_json::json!({ "id": SUBSCRIPTION_ID, "type": "complete" }),
+            )
+            .await;
+        })
+        .await;
+
+        let mut subscription = Subscription::<serde_json::Value>::start_at(&url, "token", query())
+            .await
+            .unwrap();
+        let mut ticks = Vec::new();
+        while let Some(event) = subscription.next().await {
+            ticks.push(event.unwrap()["tick"].as_u64().unwrap());
+        }
+        assert_eq!(ticks, [1, 2]);
+        assert!(subscription.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn error_frame_ends_the_subscription() {
+        let url = fake::serve_ws(|mut socket| async move {
+            accept_subscription(&mut socket).await;
+            send(
+                &mut socket,
+                serde_json::json!({
+                    "id": SUBSCRIPTION_ID,
+                    "type": "error",
+                    "payload": [{ "message": "deployment not found" }],
+                }),
+            )
+            .await;
+            // Held open, the subscription must not wait for more
+            std::future::pending::<()>().await;
+        })
+        .await;
+
+        let mut subscription = Subscription::<serde_json::Value>::start_at(&url, "token", query())
+            .await
+            .unwrap();
+        match subscription.next().await {
+            Some(Err(Error::Railway(messages))) => {
+                assert_eq!(messages, ["deployment not found"])
+            }
+            other => panic!("expected a railway error, got {other:?}"),
+        }
+        assert!(subscription.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn errors_in_next_payloads_are_surfaced() {
+        let url = fake::serve_ws(|mut socket| async move {
+            accept_subscription(&mut socket).await;
+            send(
+                &mut socket,
+                serde_json::json!({
+                    "id": SUBSCRIPTION_ID,
+                    "type": "next",
+                    "payload": { "errors": [{ "message": "not authorized" }] },
+                }),
+            )
+            .await;
+        })
+        .await;
+
+        let mut subscription = Subscription::<serde_json::Value>::start_at(&url, "token", query())
+            .await
+            .unwrap();
+        assert!(matches!(
+            subscription.next().await,
+            Some(Err(Error::Railway(messages))) if messages == ["not authorized"]
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fails_without_ack() {
+        let url = fake::serve_ws(|mut socket| async move {
+            expect(&mut socket, "connection_init").await;
+            std::future::pending::<()>().await;
+        })
+        .await;
+
+        let started = tokio::time::Instant::now();
+        let result = Subscription::<serde_json::Value>::start_at(&url, "token", query()).await;
+        assert!(
+            matches!(result, Err(Error::Subscription(message)) if message.contains("connection_ack"))
+        );
+        assert!(started.elapsed() >= ACK_TIMEOUT);
+    }
+}
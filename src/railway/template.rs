@@ -48,6 +48,7 @@ pub struct DeployedTemplate {
 }
 
 impl Template {
+    /// Every published template, with the config its services are created from
     pub async fn list(token: &str) -> Result<Vec<Template>> {
         let response: Templates = Railway::query(
             token,
@@ -97,6 +98,7 @@ impl Template {
         Ok(templates)
     }
 
+    /// Deploys services into a new project, the returned workflow tracks its creation
     pub async fn deploy(
         token: &str,
         services: Vec<NewService>,
@@ -0,0 +1,104 @@
+use crate::{Railway, Result};
+use derive_get::Getters;
+use serde::{Deserialize, Serialize};
+
+const LIST: &str = include_str!("../graphql/volume_list.gql");
+
+/// Where a volume is mounted in one environment
+#[derive(Getters, Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeInstance {
+    id: String,
+    /// `None` once the service it was attached to is deleted
+    service_id: Option<String>,
+    environment_id: String,
+    mount_path: String,
+    #[copy]
+    #[serde(rename = "currentSizeMB")]
+    current_size_mb: Option<f64>,
+}
+
+#[derive(Getters, Clone, Debug)]
+pub struct Volume {
+    id: String,
+    name: String,
+    instances: Vec<VolumeInstance>,
+}
+
+impl Volume {
+    /// Every volume of a project, with their instances across environments
+    pub async fn list(token: &str, project_id: &str) -> Result<Vec<Self>> {
+        let response: VolumeList = Railway::query(
+            token,
+            serde_json::json!({
+                "query": LIST,
+                "variables": {
+                    "id": project_id,
+                }
+            }),
+        )
+        .await?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct VolumeListProjectVolumeEdgeNodeVolumeInstancesEdge {
+            node: VolumeInstance,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct VolumeListProjectVolumeEdgeNodeVolumeInstances {
+            edges: Vec<VolumeListProjectVolumeEdgeNodeVolumeInstancesEdge>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct VolumeListProjectVolumeEdgeNode {
+            id: String,
+            name: String,
+            volume_instances: VolumeListProjectVolumeEdgeNodeVolumeInstances,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct VolumeListProjectVolumeEdge {
+            node: VolumeListProjectVolumeEdgeNode,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct VolumeListProjectVolumes {
+            edges: Vec<VolumeListProjectVolumeEdge>,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct VolumeListProject {
+            volumes: VolumeListProjectVolumes,
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct VolumeList {
+            project: VolumeListProject,
+        }
+
+        Ok(response
+            .project
+            .volumes
+            .edges
+            .into_iter()
+            .map(|volume| Volume {
+                id: volume.node.id,
+                name: volume.node.name,
+                instances: volume
+                    .node
+                    .volume_instances
+                    .edges
+                    .into_iter()
+                    .map(|i| i.node)
+                    .collect(),
+            })
+            .collect())
+    }
+}
@@ -1,3 +1,4 @@
+use super::within;
 use crate::{Railway, Result};
 use derive_get::Getters;
 use serde::{Deserialize, Serialize};
@@ -12,10 +13,17 @@ pub enum WorkflowStatus {
     Error(String),
 }
 
+/// Background job Railway runs to create a deployed template
 pub struct Workflow;
 
 impl Workflow {
-    pub async fn status(token: &str, id: &str) -> Result<WorkflowStatus> {
+    /// Waits for the workflow to finish, failing with [`crate::Error::RailwayTimeout`] after
+    /// `deadline`
+    pub async fn status(token: &str, id: &str, deadline: Duration) -> Result<WorkflowStatus> {
+        within(deadline, Self::poll_status(token, id)).await
+    }
+
+    async fn poll_status(token: &str, id: &str) -> Result<WorkflowStatus> {
         let mut interval = tokio::time::interval(Duration::from_secs(5));
         loop {
             interval.tick().await;
@@ -0,0 +1,609 @@
+use super::{escape, format_duration};
+use crate::{
+    outcome::{
+        PersistenceOutcome, RecoveryOutcome, StabilityOutcome, StageKind, StageStatus,
+        TemplateOutcome,
+    },
+    DeploymentLog, DeploymentTimeline, DomainKind, Networking, Result, Severity,
+};
+use chrono::SecondsFormat;
+use sha2::{Digest, Sha256};
+use std::{fmt::Write, path::Path};
+use strum::IntoEnumIterator;
+
+const MARKETPLACE_URL: &str = "https://railway.app/template";
+
+const STYLE: &str = "
+body { font-family: -apple-system, sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ddd; padding: 4px 10px; text-align: left; }
+th { background: #f4f4f4; }
+.passed { color: #1a7f37; }
+.flaky { color: #9a6700; }
+.failed { color: #cf222e; }
+.timed_out { color: #bc4c00; }
+.skipped { color: #777; }
+pre { background: #f6f8fa; padding: 1em; overflow-x: auto; font-size: 12px; }
+.log-error { color: #cf222e; }
+.log-warn { color: #9a6700; }
+.log-debug { color: #777; }
+";
+
+/// Self-contained HTML report: `index.html` plus one page per template
+pub struct Html;
+
+impl Html {
+    pub async fn write(dir: &Path, outcomes: &[TemplateOutcome]) -> Result<()> {
+        let templates_dir = dir.join("templates");
+        tokio::fs::create_dir_all(&templates_dir).await?;
+
+        tokio::fs::write(dir.join("index.html"), Self::index(outcomes)).await?;
+        for outcome in outcomes {
+            tokio::fs::write(
+                templates_dir.join(format!("{}.html", page_name(outcome.code()))),
+                Self::template(outcome),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    fn index(outcomes: &[TemplateOutcome]) -> String {
+        let mut body = String::new();
+        let _ = writeln!(body, "<h1>Crater run</h1>");
+
+        let _ = writeln!(body, "<h2>Summary</h2>");
+        let _ = writeln!(
+            body,
+            "<table><tr><th>Stage</th><th>Passed</th><th>Flaky</th><th>Failed</th><th>Timed out</th><th>Skipped</th></tr>"
+        );
+        // Built-in stages in pipeline order, then the custom ones in the order they ran
+        let mut stages: Vec<_> = StageKind::iter()
+            .filter(|s| !matches!(s, StageKind::Custom(_)))
+            .collect();
+        for outcome in outcomes {
+            for stage in outcome.stages() {
+                if !stages.contains(stage.stage()) {
+                    stages.push(stage.stage().clone());
+                }
+            }
+        }
+        for stage in &stages {
+            let count = |status| {
+                outcomes
+                    .iter()
+                    .filter(|o| o.stage(stage).map(|s| s.status()) == Some(status))
+                    .count()
+            };
+            let _ = writeln!(
+                body,
+                "<tr><td>{stage}</td><td class=\"passed\">{}</td><td class=\"flaky\">{}</td><td class=\"failed\">{}</td><td class=\"timed_out\">{}</td><td class=\"skipped\">{}</td></tr>",
+                count(StageStatus::Passed),
+                count(StageStatus::Flaky),
+                count(StageStatus::Failed),
+                count(StageStatus::TimedOut),
+                count(StageStatus::Skipped),
+            );
+        }
+        let _ = writeln!(body, "</table>");
+
+        let _ = writeln!(body, "<h2>Templates</h2>");
+        let _ = writeln!(
+            body,
+            "<table><tr><th>Template</th><th>Status</th><th>Failure</th><th>Duration</th><th>Build</th><th>Deploy</th><th>Marketplace</th></tr>"
+        );
+        for outcome in outcomes {
+            let status = outcome.status();
+            let failure = outcome
+                .failure()
+                .map(|s| {
+                    format!(
+                        "{}: {}",
+                        s.stage(),
+                        escape(s.error().as_deref().unwrap_or_default())
+                    )
+                })
+                .unwrap_or_default();
+            let _ = writeln!(
+                body,
+                "<tr><td><a href=\"templates/{page}.html\">{code}</a></td><td class=\"{status}\">{status}</td><td>{failure}</td><td>{}</td><td>{}</td><td>{}</td><td><a href=\"{MARKETPLACE_URL}/{path}\">{code}</a></td></tr>",
+                format_duration(outcome.duration()),
+                outcome.build_duration().map(format_duration).unwrap_or_default(),
+                outcome.deploy_duration().map(format_duration).unwrap_or_default(),
+                page = page_name(outcome.code()),
+                path = percent_encode(outcome.code()),
+                code = escape(outcome.code()),
+            );
+        }
+        let _ = writeln!(body, "</table>");
+
+        page("Crater run", &body)
+    }
+
+    fn template(outcome: &TemplateOutcome) -> String {
+        let code = escape(outcome.code());
+        let status = outcome.status();
+
+        let mut body = String::new();
+        let _ = writeln!(
+            body,
+            "<p><a href=\"../index.html\">&larr; All templates</a></p>"
+        );
+        let _ = writeln!(
+            body,
+            "<h1>{code} <span class=\"{status}\">{status}</span></h1>"
+        );
+        let _ = writeln!(
+            body,
+            "<p><a href=\"{MARKETPLACE_URL}/{}\">Marketplace page</a></p>",
+            percent_encode(outcome.code())
+        );
+        if let Some(project_id) = outcome.project_id() {
+            let _ = writeln!(body, "<p>Project: <code>{}</code></p>", escape(project_id));
+        }
+        let _ = writeln!(
+            body,
+            "<p>Started at {} and took {}</p>",
+            outcome.started_at().to_rfc3339(),
+            format_duration(outcome.duration())
+        );
+
+        let _ = writeln!(body, "<h2>Stages</h2>");
+        let _ = writeln!(
+            body,
+            "<table><tr><th>Stage</th><th>Status</th><th>Started</th><th>Duration</th><th>Error</th></tr>"
+        );
+        for stage in outcome.stages() {
+            let _ = writeln!(
+                body,
+                "<tr><td>{}</td><td class=\"{status}\">{status}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                stage.stage(),
+                stage.started_at().to_rfc3339(),
+                format_duration(stage.duration()),
+                escape(stage.error().as_deref().unwrap_or_default()),
+                status = stage.status(),
+            );
+        }
+        let _ = writeln!(body, "</table>");
+
+        let _ = writeln!(body, "<h2>Healthchecks</h2>");
+        let _ = writeln!(
+            body,
+            "<table><tr><th>Service</th><th>URL</th><th>Status code</th><th>Attempts</th><th>Latency</th><th>Error</th></tr>"
+        );
+        for service in outcome.services() {
+            let Some(healthcheck) = service.healthcheck() else {
+                continue;
+            };
+            let class = if healthcheck.is_healthy() {
+                "passed"
+            } else {
+                "failed"
+            };
+            let _ = writeln!(
+                body,
+                "<tr><td>{}</td><td><a href=\"{url}\">{url}</a></td><td class=\"{class}\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(service.name()),
+                healthcheck
+                    .status_code()
+                    .map(|c| c.to_string())
+                    .unwrap_or_default(),
+                healthcheck.attempts(),
+                healthcheck
+                    .latency()
+                    .map(format_duration)
+                    .unwrap_or_default(),
+                escape(healthcheck.error().as_deref().unwrap_or_default()),
+                url = escape(healthcheck.url()),
+            );
+        }
+        for service in outcome.services() {
+            for probe in service.probes() {
+                let (class, result) = if probe.is_healthy() {
+                    ("passed", "connected")
+                } else {
+                    ("failed", "unreachable")
+                };
+                let _ = writeln!(
+                    body,
+                    "<tr><td>{}</td><td><code>{}</code></td><td class=\"{class}\">{result}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape(service.name()),
+                    escape(&probe.target()),
+                    probe.attempts(),
+                    probe.latency().map(format_duration).unwrap_or_default(),
+                    escape(probe.error().as_deref().unwrap_or_default()),
+                );
+            }
+        }
+        let _ = writeln!(body, "</table>");
+
+        if !outcome.smoke_tests().is_empty() {
+            let _ = writeln!(body, "<h2>Smoke tests</h2>");
+            let _ = writeln!(
+                body,
+                "<table><tr><th>Test</th><th>Request</th><th>Status code</th><th>Latency</th><th>Assertions</th></tr>"
+            );
+            for smoke_test in outcome.smoke_tests() {
+                let assertions: Vec<_> = smoke_test
+                    .assertions()
+                    .iter()
+                    .map(|a| {
+                        format!(
+                            "<span class=\"{}\">{}</span>{}",
+                            if a.passed() { "passed" } else { "failed" },
+                            escape(a.description()),
+                            a.actual()
+                                .as_ref()
+                                .map(|actual| format!(" (got <code>{}</code>)", escape(actual)))
+                                .unwrap_or_default(),
+                        )
+                    })
+                    .chain(
+                        smoke_test
+                            .error()
+                            .iter()
+                            .map(|err| format!("<span class=\"failed\">{}</span>", escape(err))),
+                    )
+                    .collect();
+                let _ = writeln!(
+                    body,
+                    "<tr><td class=\"{}\">{}</td><td><code>{} {}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    if smoke_test.is_passed() {
+                        "passed"
+                    } else {
+                        "failed"
+                    },
+                    escape(smoke_test.name()),
+                    escape(smoke_test.method()),
+                    escape(smoke_test.url()),
+                    smoke_test
+                        .status_code()
+                        .map(|c| c.to_string())
+                        .unwrap_or_default(),
+                    smoke_test.latency().map(format_duration).unwrap_or_default(),
+                    assertions.join("<br>"),
+                );
+            }
+            let _ = writeln!(body, "</table>");
+        }
+
+        if let Some(script) = outcome.script() {
+            let _ = writeln!(body, "<h2>Check script</h2>");
+            let _ = writeln!(
+                body,
+                "<p class=\"{}\"><code>{}</code> {} after {}</p>",
+                if script.is_passed() {
+                    "passed"
+                } else {
+                    "failed"
+                },
+                escape(script.command()),
+                match script.exit_code() {
+                    _ if script.timed_out() => "timed out".to_owned(),
+                    Some(code) => format!("exited with {code}"),
+                    None => "was killed by a signal".to_owned(),
+                },
+                format_duration(script.duration()),
+            );
+            for (name, output) in [("stdout", script.stdout()), ("stderr", script.stderr())] {
+                if !output.is_empty() {
+                    let _ = writeln!(body, "<h3>{name}</h3><pre>{}</pre>", escape(output));
+                }
+            }
+        }
+
+        for service in outcome.services() {
+            let _ = writeln!(body, "<h2>Service {}</h2>", escape(service.name()));
+            let _ = writeln!(
+                body,
+                "<p>Status: <code>{}</code>, deployment: <code>{}</code>, url: <code>{}</code></p>",
+                escape(service.status().as_ref().map_or("unknown", |s| s.as_str())),
+                escape(service.deployment_id().as_deref().unwrap_or("none")),
+                escape(service.static_url().as_deref().unwrap_or("none")),
+            );
+            if let Some(networking) = service.networking() {
+                write_networking(&mut body, networking);
+            }
+            if let Some(stability) = service.stability() {
+                write_stability(&mut body, stability);
+            }
+            if let Some(persistence) = service.persistence() {
+                write_persistence(&mut body, persistence);
+            }
+            if !service.recoveries().is_empty() {
+                write_recoveries(&mut body, service.recoveries());
+            }
+            if let Some(timeline) = service.timeline() {
+                write_timeline(&mut body, timeline);
+            }
+            let _ = writeln!(body, "<h3>Build logs</h3>");
+            if service.build_logs_truncated() {
+                let _ = writeln!(
+                    body,
+                    "<p class=\"log-warn\">Cut off before the build finished</p>"
+                );
+            }
+            write_logs(&mut body, service.build_logs());
+            let _ = writeln!(body, "<h3>Deploy logs</h3>");
+            if service.deploy_logs_truncated() {
+                let _ = writeln!(
+                    body,
+                    "<p class=\"log-warn\">Cut off before the deployment settled</p>"
+                );
+            }
+            write_logs(&mut body, service.deploy_logs());
+        }
+
+        page(&code, &body)
+    }
+}
+
+fn write_networking(body: &mut String, networking: &Networking) {
+    if networking.domains().is_empty() && networking.tcp_proxies().is_empty() {
+        return;
+    }
+
+    let _ = writeln!(body, "<h3>Networking</h3>");
+    let _ = writeln!(body, "<ul>");
+    for domain in networking.domains() {
+        let _ = writeln!(
+            body,
+            "<li>{} domain <code>{}</code>{}</li>",
+            match domain.kind() {
+                DomainKind::Service => "Service",
+                DomainKind::Custom => "Custom",
+            },
+            escape(domain.domain()),
+            domain
+                .target_port()
+                .map(|port| format!(" &rarr; port {port}"))
+                .unwrap_or_default(),
+        );
+    }
+    for proxy in networking.tcp_proxies() {
+        let _ = writeln!(
+            body,
+            "<li>TCP proxy <code>{}</code> &rarr; port {}</li>",
+            escape(&proxy.address()),
+            proxy.application_port(),
+        );
+    }
+    let _ = writeln!(body, "</ul>");
+}
+
+fn write_stability(body: &mut String, stability: &StabilityOutcome) {
+    let _ = writeln!(body, "<h3>Stability</h3>");
+    let _ = writeln!(
+        body,
+        "<p class=\"{}\">{} for {}: {} crash(es), {} restart(s)</p>",
+        if stability.is_stable() {
+            "passed"
+        } else {
+            "failed"
+        },
+        if stability.is_stable() {
+            "Stable"
+        } else {
+            "Unstable"
+        },
+        format_duration(stability.window()),
+        stability.crashes().len(),
+        stability.restarts(),
+    );
+    for crash in stability.crashes() {
+        let _ = writeln!(
+            body,
+            "<h4>{} at {}</h4>",
+            escape(crash.status().as_str()),
+            crash.at().to_rfc3339()
+        );
+        write_logs(body, crash.logs());
+    }
+}
+
+fn write_persistence(body: &mut String, persistence: &PersistenceOutcome) {
+    let _ = writeln!(body, "<h3>Persistence</h3>");
+    let _ = writeln!(
+        body,
+        "<p class=\"{}\">{} over {}, redeployment: <code>{}</code> ({})</p>",
+        if persistence.persisted() {
+            "passed"
+        } else {
+            "failed"
+        },
+        if persistence.persisted() {
+            "Sentinel kept"
+        } else {
+            "Sentinel lost"
+        },
+        escape(persistence.protocol()),
+        escape(persistence.redeployment_id().as_deref().unwrap_or("none")),
+        escape(
+            persistence
+                .status()
+                .as_ref()
+                .map_or("unknown", |s| s.as_str())
+        ),
+    );
+    if let Some(error) = persistence.error() {
+        let _ = writeln!(body, "<p>{}</p>", escape(error));
+    }
+}
+
+fn write_recoveries(body: &mut String, recoveries: &[RecoveryOutcome]) {
+    let _ = writeln!(body, "<h3>Restarts and redeploys</h3>");
+    let _ = writeln!(
+        body,
+        "<table><tr><th>Action</th><th>Deployment</th><th>Status</th><th>Checks</th><th>Error</th></tr>"
+    );
+    for recovery in recoveries {
+        let checks: Vec<_> = recovery
+            .healthcheck()
+            .iter()
+            .map(|h| {
+                let result = if h.is_healthy() {
+                    "healthy"
+                } else {
+                    "unhealthy"
+                };
+                format!("{} {result}", escape(h.url()))
+            })
+            .chain(recovery.probes().iter().map(|p| {
+                let result = if p.is_healthy() {
+                    "connected"
+                } else {
+                    "unreachable"
+                };
+                format!("<code>{}</code> {result}", escape(&p.target()))
+            }))
+            .collect();
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td><code>{}</code></td><td class=\"{}\">{}</td><td>{}</td><td>{}</td></tr>",
+            recovery.action(),
+            escape(recovery.deployment_id().as_deref().unwrap_or("none")),
+            if recovery.is_healthy() {
+                "passed"
+            } else {
+                "failed"
+            },
+            escape(recovery.status().as_ref().map_or("unknown", |s| s.as_str())),
+            checks.join("<br>"),
+            escape(recovery.error().as_deref().unwrap_or_default()),
+        );
+    }
+    let _ = writeln!(body, "</table>");
+}
+
+fn write_timeline(body: &mut String, timeline: &DeploymentTimeline) {
+    let _ = writeln!(body, "<h3>Timeline</h3>");
+    let _ = writeln!(
+        body,
+        "<p>Build: {}, deploy: {}</p>",
+        timeline
+            .build_duration()
+            .map(format_duration)
+            .unwrap_or_else(|| "n/a".to_owned()),
+        timeline
+            .deploy_duration()
+            .map(format_duration)
+            .unwrap_or_else(|| "n/a".to_owned()),
+    );
+
+    let _ = writeln!(
+        body,
+        "<table><tr><th>Step</th><th>Started</th><th>Completed</th><th>Error</th></tr>"
+    );
+    let _ = writeln!(
+        body,
+        "<tr><td>CREATED</td><td>{}</td><td></td><td></td></tr>",
+        timeline.created_at().to_rfc3339()
+    );
+    for event in timeline.events() {
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(event.step()),
+            event.created_at().to_rfc3339(),
+            event
+                .completed_at()
+                .map(|c| c.to_rfc3339())
+                .unwrap_or_default(),
+            escape(event.error().as_deref().unwrap_or_default()),
+        );
+    }
+    if let Some(updated_at) = timeline.status_updated_at() {
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td></td><td></td></tr>",
+            escape(timeline.status().as_str()),
+            updated_at.to_rfc3339()
+        );
+    }
+    let _ = writeln!(body, "</table>");
+}
+
+fn write_logs(body: &mut String, logs: &[DeploymentLog]) {
+    if logs.is_empty() {
+        let _ = writeln!(body, "<p><em>No logs</em></p>");
+        return;
+    }
+
+    let errors = DeploymentLog::errors(logs).count();
+    if errors > 0 {
+        let _ = writeln!(body, "<p class=\"log-error\">{errors} error line(s)</p>");
+    }
+
+    let _ = writeln!(body, "<pre>");
+    for log in logs {
+        let class = match log.severity() {
+            Severity::Error => "log-error",
+            Severity::Warn => "log-warn",
+            Severity::Debug => "log-debug",
+            Severity::Info => "log-info",
+        };
+        let _ = writeln!(
+            body,
+            "<span class=\"{class}\">{} {}</span>",
+            log.timestamp().to_rfc3339_opts(SecondsFormat::Millis, true),
+            escape(log.message())
+        );
+    }
+    let _ = writeln!(body, "</pre>");
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n{body}</body>\n</html>\n"
+    )
+}
+
+/// File name of a template's page, the hash keeps codes that sanitise alike apart
+fn page_name(code: &str) -> String {
+    let sanitised: String = code
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let hash = Sha256::digest(code.as_bytes());
+    format!(
+        "{sanitised}-{:02x}{:02x}{:02x}{:02x}",
+        hash[0], hash[1], hash[2], hash[3]
+    )
+}
+
+/// Percent-encodes a URL path segment, leaving only unreserved characters as they are
+fn percent_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_names_dont_collide() {
+        assert_ne!(page_name("a/b"), page_name("a_b"));
+        assert!(page_name("a/b").starts_with("a_b-"));
+    }
+
+    #[test]
+    fn marketplace_path_is_percent_encoded() {
+        assert_eq!(percent_encode("my-app"), "my-app");
+        assert_eq!(percent_encode("a b/?#é"), "a%20b%2F%3F%23%C3%A9");
+    }
+}
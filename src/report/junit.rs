@@ -0,0 +1,92 @@
+use super::escape;
+use crate::{
+    outcome::{StageStatus, TemplateOutcome},
+    Result,
+};
+use chrono::SecondsFormat;
+use std::{fmt::Write, path::Path};
+
+/// JUnit XML with one testcase per template, for CI test UIs
+pub struct Junit;
+
+impl Junit {
+    pub async fn write(dir: &Path, outcomes: &[TemplateOutcome]) -> Result<()> {
+        tokio::fs::write(dir.join("junit.xml"), Self::render(outcomes)).await?;
+        Ok(())
+    }
+
+    pub fn render(outcomes: &[TemplateOutcome]) -> String {
+        let count = |status| outcomes.iter().filter(|o| o.status() == status).count();
+        let failures = outcomes.iter().filter(|o| o.status().is_failure()).count();
+        let time: f64 = outcomes.iter().map(|o| o.duration().as_secs_f64()).sum();
+
+        let mut xml = String::new();
+        let _ = writeln!(xml, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+        let _ = writeln!(
+            xml,
+            "<testsuites name=\"crater\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{time:.3}\">",
+            outcomes.len(),
+            failures,
+            count(StageStatus::Skipped),
+        );
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"templates\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{time:.3}\">",
+            outcomes.len(),
+            failures,
+            count(StageStatus::Skipped),
+        );
+
+        for outcome in outcomes {
+            let _ = writeln!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"crater.templates\" time=\"{:.3}\">",
+                escape(outcome.code()),
+                outcome.duration().as_secs_f64(),
+            );
+
+            match outcome.status() {
+                StageStatus::Failed | StageStatus::TimedOut => {
+                    if let Some(failure) = outcome.failure() {
+                        let message = format!(
+                            "{} {}: {}",
+                            failure.stage(),
+                            failure.status(),
+                            failure.error().as_deref().unwrap_or("unknown error")
+                        );
+                        let _ = writeln!(
+                            xml,
+                            "      <failure message=\"{message}\" type=\"{}\">{message}</failure>",
+                            failure.status(),
+                            message = escape(&message),
+                        );
+                    }
+                }
+                StageStatus::Skipped => {
+                    let _ = writeln!(xml, "      <skipped/>");
+                }
+                StageStatus::Passed | StageStatus::Flaky => {}
+            }
+
+            let _ = write!(xml, "      <system-out>");
+            for service in outcome.services() {
+                let _ = writeln!(xml, "== {} ==", escape(service.name()));
+                for log in service.build_logs() {
+                    let _ = writeln!(
+                        xml,
+                        "{} {}",
+                        log.timestamp().to_rfc3339_opts(SecondsFormat::Millis, true),
+                        escape(log.message())
+                    );
+                }
+            }
+            let _ = writeln!(xml, "</system-out>");
+
+            let _ = writeln!(xml, "    </testcase>");
+        }
+
+        let _ = writeln!(xml, "  </testsuite>");
+        let _ = writeln!(xml, "</testsuites>");
+        xml
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{
+    outcome::{StageStatus, TemplateOutcome},
+    Result,
+};
+use std::{fmt::Write, path::Path};
+
+/// Test Anything Protocol (version 13) output, one test point per template
+pub struct Tap;
+
+impl Tap {
+    pub async fn write(dir: &Path, outcomes: &[TemplateOutcome]) -> Result<()> {
+        tokio::fs::write(dir.join("results.tap"), Self::render(outcomes)).await?;
+        Ok(())
+    }
+
+    pub fn render(outcomes: &[TemplateOutcome]) -> String {
+        let mut tap = String::new();
+        let _ = writeln!(tap, "TAP version 13");
+        let _ = writeln!(tap, "1..{}", outcomes.len());
+
+        for (index, outcome) in outcomes.iter().enumerate() {
+            let number = index + 1;
+            let code = outcome.code().replace('#', "\\#");
+            match outcome.status() {
+                StageStatus::Passed => {
+                    let _ = writeln!(tap, "ok {number} - {code}");
+                }
+                StageStatus::Flaky => {
+                    let _ = writeln!(tap, "ok {number} - {code} # flaky");
+                }
+                StageStatus::Skipped => {
+                    let _ = writeln!(tap, "ok {number} - {code} # SKIP no deployable config");
+                }
+                StageStatus::Failed | StageStatus::TimedOut => {
+                    let _ = writeln!(tap, "not ok {number} - {code}");
+                    if let Some(failure) = outcome.failure() {
+                        let _ = writeln!(tap, "  ---");
+                        let _ = writeln!(tap, "  stage: {}", failure.stage());
+                        let _ = writeln!(tap, "  status: {}", failure.status());
+                        let _ = writeln!(
+                            tap,
+                            "  message: {}",
+                            serde_json::Value::from(
+                                failure.error().as_deref().unwrap_or("unknown error")
+                            )
+                        );
+                        let _ = writeln!(tap, "  duration_ms: {}", outcome.duration().as_millis());
+                        let _ = writeln!(tap, "  ...");
+                    }
+                }
+            }
+        }
+
+        tap
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    healthcheck::Healthcheck,
+    outcome::{RecoveryAction, RecoveryOutcome},
+    probe::{Probe, Probes},
+    Deployment, DeploymentStatus, Result, Service,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// A restarted deployment keeps its status, give the old container time to go away
+/// before checking it again
+const RESTART_GRACE: Duration = Duration::from_secs(15);
+
+/// What a service passed on its first deploy, checked again after every recovery
+pub struct Checks {
+    pub service_id: String,
+    pub environment_id: String,
+    /// URL and timeout of the HTTP healthcheck
+    pub healthcheck: Option<(String, Duration)>,
+    pub probe: Arc<dyn Probe>,
+    pub addresses: Vec<String>,
+    pub variables: HashMap<String, String>,
+}
+
+pub struct Resilience;
+
+impl Resilience {
+    /// Restarts the deployment then redeploys the service, waiting for each to settle
+    /// and running the checks again
+    pub async fn verify(
+        token: &str,
+        deployment_id: &str,
+        checks: &Checks,
+        timeout: Duration,
+    ) -> Result<Vec<RecoveryOutcome>> {
+        let deadline = Instant::now() + timeout;
+
+        info!("Restarting {deployment_id}");
+        Deployment::restart(token, deployment_id).await?;
+        tokio::time::sleep(RESTART_GRACE).await;
+        let status = Deployment::terminal_status(token, deployment_id).await?;
+        let restart = Self::recheck(
+            RecoveryAction::Restart,
+            deployment_id.to_owned(),
+            status,
+            checks,
+            deadline,
+        )
+        .await;
+
+        info!("Redeploying service {}", checks.service_id);
+        let redeployment_id = Service::redeploy(
+            token,
+            &checks.service_id,
+            &checks.environment_id,
+            deadline.saturating_duration_since(Instant::now()),
+        )
+        .await?;
+        let status = Deployment::terminal_status(token, &redeployment_id).await?;
+        let redeploy = Self::recheck(
+            RecoveryAction::Redeploy,
+            redeployment_id,
+            status,
+            checks,
+            deadline,
+        )
+        .await;
+
+        Ok(vec![restart, redeploy])
+    }
+
+    async fn recheck(
+        action: RecoveryAction,
+        deployment_id: String,
+        status: DeploymentStatus,
+        checks: &Checks,
+        deadline: Instant,
+    ) -> RecoveryOutcome {
+        let mut outcome = RecoveryOutcome {
+            action,
+            deployment_id: Some(deployment_id),
+            status: Some(status.clone()),
+            healthcheck: None,
+            probes: Vec::new(),
+            error: None,
+        };
+        if !status.is_success() {
+            warn!(
+                "{action} of {} ended with {}",
+                checks.service_id,
+                status.as_str()
+            );
+            outcome.error = Some(format!("{action} ended with {}", status.as_str()));
+            return outcome;
+        }
+
+        if let Some((url, timeout)) = &checks.healthcheck {
+            let timeout = (*timeout).min(deadline.saturating_duration_since(Instant::now()));
+            outcome.healthcheck = Some(Healthcheck::check(url.clone(), timeout).await);
+        }
+        for address in &checks.addresses {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            outcome.probes.push(
+                Probes::check(
+                    checks.probe.as_ref(),
+                    address.clone(),
+                    &checks.variables,
+                    timeout,
+                )
+                .await,
+            );
+        }
+
+        outcome
+    }
+}
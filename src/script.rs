@@ -0,0 +1,244 @@
+use crate::{config::ScriptConfig, outcome::ScriptOutcome, Result};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::{Mutex, OnceLock, PoisonError},
+    time::Instant,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::Command,
+};
+use tracing::info;
+
+/// Only the end of the output is kept, that's where failures are reported
+const MAX_OUTPUT_LEN: usize = 64 * 1024;
+
+/// Deployed project as described to check scripts, both as `CRATER_CONTEXT` JSON and as
+/// flat `CRATER_*` variables
+#[derive(Serialize, Debug, Clone)]
+pub struct ScriptContext {
+    pub template: String,
+    pub project_id: String,
+    pub services: Vec<ScriptService>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ScriptService {
+    pub name: String,
+    /// Public URL, `https://` included
+    pub url: Option<String>,
+    /// `host:port` of every TCP proxy
+    pub tcp_proxies: Vec<String>,
+    pub variables: HashMap<String, String>,
+}
+
+impl ScriptContext {
+    /// `CRATER_SERVICE_{NAME}_URL`, `_TCP_PROXIES` and `_VAR_{VARIABLE}` for each service,
+    /// with names upper cased and anything but letters and digits replaced by `_`
+    fn env(&self) -> Result<Vec<(String, String)>> {
+        let mut env = vec![
+            ("CRATER_CONTEXT".to_owned(), serde_json::to_string(self)?),
+            ("CRATER_TEMPLATE".to_owned(), self.template.clone()),
+            ("CRATER_PROJECT_ID".to_owned(), self.project_id.clone()),
+            (
+                "CRATER_SERVICES".to_owned(),
+                self.services
+                    .iter()
+                    .map(|s| s.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+        ];
+
+        for service in &self.services {
+            let prefix = format!("CRATER_SERVICE_{}", env_name(&service.name));
+            if let Some(url) = &service.url {
+                env.push((format!("{prefix}_URL"), url.clone()));
+            }
+            env.push((
+                format!("{prefix}_TCP_PROXIES"),
+                service.tcp_proxies.join(","),
+            ));
+            for (name, value) in &service.variables {
+                env.push((format!("{prefix}_VAR_{}", env_name(name)), value.clone()));
+            }
+        }
+
+        Ok(env)
+    }
+}
+
+/// What a script wrote so far, owned by the caller so a run cut off by a timeout still has
+/// the output leading up to it
+#[derive(Default)]
+pub struct ScriptOutput {
+    started: OnceLock<Instant>,
+    stdout: Mutex<Vec<u8>>,
+    stderr: Mutex<Vec<u8>>,
+}
+
+impl ScriptOutput {
+    /// Outcome of a run dropped before it exited, `None` if the script never started
+    pub fn timed_out(&self, config: &ScriptConfig) -> Option<ScriptOutcome> {
+        let started = self.started.get()?;
+        Some(ScriptOutcome {
+            command: config.command.clone(),
+            exit_code: None,
+            timed_out: true,
+            duration: started.elapsed(),
+            stdout: tail(&self.stdout.lock().unwrap_or_else(PoisonError::into_inner)),
+            stderr: tail(&self.stderr.lock().unwrap_or_else(PoisonError::into_inner)),
+        })
+    }
+}
+
+pub struct Script;
+
+impl Script {
+    /// Runs the configured executable to completion, writing its output to `output` as it
+    /// goes. The caller bounds how long it may take and dropping the future kills it
+    pub async fn run(
+        config: &ScriptConfig,
+        context: &ScriptContext,
+        output: &ScriptOutput,
+    ) -> Result<ScriptOutcome> {
+        info!("Running {} for {}", config.command, context.template);
+        let mut command = std::process::Command::new(&config.command);
+        command
+            .args(&config.args)
+            .envs(context.env()?)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Scripts run their own tools, a group lets a timeout take those down too
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+        let mut child = Command::from(command).kill_on_drop(true).spawn()?;
+        let started = *output.started.get_or_init(Instant::now);
+        let _group = ProcessGroup(child.id());
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let (status, (), ()) = tokio::try_join!(
+            child.wait(),
+            capture(stdout, &output.stdout),
+            capture(stderr, &output.stderr),
+        )?;
+
+        Ok(ScriptOutcome {
+            command: config.command.clone(),
+            exit_code: status.code(),
+            timed_out: false,
+            duration: started.elapsed(),
+            stdout: tail(&output.stdout.lock().unwrap_or_else(PoisonError::into_inner)),
+            stderr: tail(&output.stderr.lock().unwrap_or_else(PoisonError::into_inner)),
+        })
+    }
+}
+
+/// Appends everything read from `pipe` to `buffer`, dropping what [`tail`] won't keep
+async fn capture(mut pipe: impl AsyncRead + Unpin, buffer: &Mutex<Vec<u8>>) -> std::io::Result<()> {
+    let mut chunk = [0; 8 * 1024];
+    loop {
+        let read = pipe.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
+        }
+
+        let mut buffer = buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        buffer.extend_from_slice(&chunk[..read]);
+        if buffer.len() > 2 * MAX_OUTPUT_LEN {
+            let excess = buffer.len() - MAX_OUTPUT_LEN;
+            buffer.drain(..excess);
+        }
+    }
+}
+
+/// Kills whatever the script left running once it exits or is dropped
+struct ProcessGroup(Option<u32>);
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(id) = self.0.and_then(|id| libc::pid_t::try_from(id).ok()) {
+            // SAFETY: only signals the group the script was spawned in
+            unsafe {
+                libc::kill(-id, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+fn env_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn tail(output: &[u8]) -> String {
+    let start = output.len().saturating_sub(MAX_OUTPUT_LEN);
+    String::from_utf8_lossy(&output[start..]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn shell(script: &str) -> ScriptConfig {
+        ScriptConfig {
+            template: "template".to_owned(),
+            command: "sh".to_owned(),
+            args: vec!["-c".to_owned(), script.to_owned()],
+        }
+    }
+
+    fn context() -> ScriptContext {
+        ScriptContext {
+            template: "template".to_owned(),
+            project_id: "project".to_owned(),
+            services: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn output_is_captured() {
+        let config = shell("echo out; echo err >&2; exit 3");
+        let output = ScriptOutput::default();
+        let outcome = Script::run(&config, &context(), &output).await.unwrap();
+        assert_eq!(outcome.exit_code, Some(3));
+        assert!(!outcome.timed_out);
+        assert_eq!(outcome.stdout, "out\n");
+        assert_eq!(outcome.stderr, "err\n");
+    }
+
+    #[tokio::test]
+    async fn timed_out_runs_keep_their_output() {
+        let config = shell("echo $CRATER_PROJECT_ID; echo waiting >&2; sleep 30");
+        let output = ScriptOutput::default();
+        let context = context();
+        let run = Script::run(&config, &context, &output);
+        assert!(tokio::time::timeout(Duration::from_millis(500), run)
+            .await
+            .is_err());
+
+        let outcome = output.timed_out(&config).unwrap();
+        assert!(outcome.timed_out);
+        assert_eq!(outcome.exit_code, None);
+        assert_eq!(outcome.stdout, "project\n");
+        assert_eq!(outcome.stderr, "waiting\n");
+    }
+
+    #[test]
+    fn scripts_that_never_started_have_no_outcome() {
+        assert!(ScriptOutput::default().timed_out(&shell("true")).is_none());
+    }
+}
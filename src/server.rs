@@ -0,0 +1,225 @@
+use crate::{metrics, outcome::RunSummary, signature, Config, Error, Result};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{mpsc, RwLock};
+use tracing::{error, info, warn};
+
+/// Finished jobs beyond this are forgotten, oldest first
+const MAX_JOBS: usize = 1000;
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Job {
+    id: u64,
+    templates: Vec<String>,
+    status: JobStatus,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+    summary: Option<RunSummary>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TriggerRequest {
+    templates: Vec<String>,
+}
+
+struct ServerState {
+    secret: String,
+    next_id: AtomicU64,
+    jobs: RwLock<BTreeMap<u64, Job>>,
+    queue: mpsc::UnboundedSender<u64>,
+}
+
+/// Runs crater as a long-lived service, triggered by signed webhooks
+pub async fn serve(token: String, config: Config) -> Result<()> {
+    let secret = config
+        .server
+        .secret
+        .clone()
+        .ok_or(Error::MissingConfig("server.secret"))?;
+    let address: SocketAddr = config
+        .server
+        .address
+        .parse()
+        .map_err(|err| Error::InvalidAddress(err, config.server.address.clone()))?;
+
+    let (queue, receiver) = mpsc::unbounded_channel();
+    let state = Arc::new(ServerState {
+        secret,
+        next_id: AtomicU64::new(1),
+        jobs: RwLock::new(BTreeMap::new()),
+        queue,
+    });
+
+    tokio::spawn(worker(token, config, state.clone(), receiver));
+
+    let app = Router::new()
+        .route("/webhook", post(trigger))
+        .route("/jobs", get(jobs))
+        .route("/jobs/:id", get(job))
+        .route("/metrics", get(metrics::handler))
+        .with_state(state);
+
+    info!("Listening on {address}");
+    axum::Server::bind(&address)
+        .serve(app.into_make_service())
+        .await
+        .map_err(|err| Error::Server(err.to_string()))
+}
+
+/// Runs queued jobs one at a time, each run already fans out to its own workers
+async fn worker(
+    token: String,
+    config: Config,
+    state: Arc<ServerState>,
+    mut receiver: mpsc::UnboundedReceiver<u64>,
+) {
+    while let Some(id) = receiver.recv().await {
+        let templates = {
+            let mut jobs = state.jobs.write().await;
+            let Some(job) = jobs.get_mut(&id) else {
+                continue;
+            };
+            job.status = JobStatus::Running;
+            job.started_at = Some(Utc::now());
+            job.templates.clone()
+        };
+
+        info!("Running job {id} for {templates:?}");
+        let result = crate::run(
+            token.clone(),
+            Config {
+                templates,
+                ..config.clone()
+            },
+        )
+        .await;
+
+        let mut jobs = state.jobs.write().await;
+        let Some(job) = jobs.get_mut(&id) else {
+            continue;
+        };
+        job.finished_at = Some(Utc::now());
+        match result {
+            Ok(summary) => {
+                job.status = JobStatus::Finished;
+                job.summary = Some(summary);
+            }
+            Err(err) => {
+                error!("Job {id} failed: {err}");
+                job.status = JobStatus::Failed;
+                job.error = Some(err.to_string());
+            }
+        }
+    }
+}
+
+async fn trigger(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let header = headers
+        .get(signature::HEADER)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+    match signature::verify(state.secret.as_bytes(), &body, header) {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!("Rejected webhook with invalid signature");
+            return error_response(StatusCode::UNAUTHORIZED, "invalid signature");
+        }
+        Err(err) => {
+            error!("Unable to verify webhook signature: {err}");
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "unable to verify");
+        }
+    }
+
+    let request: TriggerRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(err) => return error_response(StatusCode::BAD_REQUEST, &err.to_string()),
+    };
+    if request.templates.is_empty() {
+        return error_response(StatusCode::BAD_REQUEST, "no templates to run");
+    }
+
+    let id = state.next_id.fetch_add(1, Ordering::Relaxed);
+    let job = Job {
+        id,
+        templates: request.templates,
+        status: JobStatus::Queued,
+        created_at: Utc::now(),
+        started_at: None,
+        finished_at: None,
+        summary: None,
+        error: None,
+    };
+
+    {
+        let mut jobs = state.jobs.write().await;
+        jobs.insert(id, job);
+        while jobs.len() > MAX_JOBS {
+            let Some(oldest) = jobs
+                .iter()
+                .find(|(_, j)| matches!(j.status, JobStatus::Finished | JobStatus::Failed))
+                .map(|(id, _)| *id)
+            else {
+                break;
+            };
+            jobs.remove(&oldest);
+        }
+    }
+
+    if state.queue.send(id).is_err() {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "job queue is closed");
+    }
+
+    info!("Queued job {id}");
+    (StatusCode::ACCEPTED, Json(serde_json::json!({ "id": id })))
+}
+
+async fn jobs(State(state): State<Arc<ServerState>>) -> Json<Vec<Job>> {
+    Json(state.jobs.read().await.values().cloned().collect())
+}
+
+async fn job(
+    State(state): State<Arc<ServerState>>,
+    Path(id): Path<u64>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    match state.jobs.read().await.get(&id) {
+        Some(job) => (
+            StatusCode::OK,
+            Json(serde_json::to_value(job).unwrap_or_default()),
+        ),
+        None => error_response(StatusCode::NOT_FOUND, "job not found"),
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> (StatusCode, Json<serde_json::Value>) {
+    (status, Json(serde_json::json!({ "error": message })))
+}
@@ -0,0 +1,46 @@
+use crate::Result;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt::Write;
+
+pub const HEADER: &str = "X-Crater-Signature";
+
+/// Prefix used in the signature headers, as in `sha256=<hex>`
+pub const PREFIX: &str = "sha256=";
+
+/// Hex encoded HMAC-SHA256 of `body`
+pub fn sign(secret: &[u8], body: &[u8]) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret)?;
+    mac.update(body);
+    Ok(hex(&mac.finalize().into_bytes()))
+}
+
+/// Checks a `sha256=<hex>` header value against `body` in constant time
+pub fn verify(secret: &[u8], body: &[u8], header: &str) -> Result<bool> {
+    let Some(signature) = header.strip_prefix(PREFIX).and_then(unhex) else {
+        return Ok(false);
+    };
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret)?;
+    mac.update(body);
+    Ok(mac.verify_slice(&signature).is_ok())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
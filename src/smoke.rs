@@ -0,0 +1,488 @@
+use crate::{
+    outcome::{AssertionOutcome, SmokeTestOutcome},
+    Error, Result,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::Path,
+    time::{Duration, Instant},
+};
+use tracing::{debug, info};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Requests and assertions maintainers write for a template, read from `{code}.json`
+/// in [`crate::Config::smoke_tests_dir`]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct SmokeTests {
+    pub tests: Vec<SmokeTest>,
+}
+
+/// A single request, every string in it can reference variables as `${{service.NAME}}`,
+/// or `${{NAME}}` for the variables of the test's own service
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct SmokeTest {
+    pub name: String,
+    /// Service whose public domain `path` is relative to
+    #[serde(default)]
+    pub service: Option<String>,
+    /// Full URL, overriding `service` and `path`
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Expected status code, any 2xx when unset
+    #[serde(default)]
+    pub status: Option<u16>,
+    #[serde(default)]
+    pub expect_headers: HashMap<String, Matcher>,
+    #[serde(default)]
+    pub expect_body: Vec<Matcher>,
+    #[serde(default)]
+    pub expect_json: Vec<JsonAssertion>,
+}
+
+fn default_path() -> String {
+    "/".to_owned()
+}
+
+fn default_method() -> String {
+    "GET".to_owned()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Matcher {
+    Equals(String),
+    Contains(String),
+    NotContains(String),
+}
+
+impl Matcher {
+    fn matches(&self, actual: &str) -> bool {
+        match self {
+            Self::Equals(expected) => actual == expected,
+            Self::Contains(expected) => actual.contains(expected.as_str()),
+            Self::NotContains(expected) => !actual.contains(expected.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Equals(expected) => write!(f, "equals {expected:?}"),
+            Self::Contains(expected) => write!(f, "contains {expected:?}"),
+            Self::NotContains(expected) => write!(f, "doesn't contain {expected:?}"),
+        }
+    }
+}
+
+/// Assertion on the value a JSONPath such as `$.data.items[0].name` selects
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct JsonAssertion {
+    pub path: String,
+    #[serde(flatten)]
+    pub expect: JsonExpectation,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonExpectation {
+    Equals(Value),
+    Exists(bool),
+    /// Substring of a string value
+    Contains(String),
+}
+
+/// What smoke tests can reference from a deployed service
+#[derive(Debug, Clone, Default)]
+pub struct SmokeService {
+    pub static_url: Option<String>,
+    pub variables: HashMap<String, String>,
+}
+
+impl SmokeTests {
+    /// `None` when the template has no smoke tests
+    pub async fn load(dir: &Path, code: &str) -> Result<Option<Self>> {
+        let path = dir.join(format!("{code}.json"));
+        let json = match tokio::fs::read_to_string(&path).await {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|err| Error::SmokeTests(err, path.display().to_string()))
+    }
+}
+
+impl SmokeTest {
+    pub async fn run(
+        &self,
+        client: &reqwest::Client,
+        services: &HashMap<String, SmokeService>,
+    ) -> SmokeTestOutcome {
+        let mut outcome = SmokeTestOutcome {
+            name: self.name.clone(),
+            method: self.method.to_uppercase(),
+            url: String::new(),
+            status_code: None,
+            latency: None,
+            assertions: Vec::new(),
+            error: None,
+        };
+
+        let request = match self.request(client, services) {
+            Ok((url, request)) => {
+                outcome.url = url;
+                request
+            }
+            Err(err) => {
+                outcome.error = Some(err);
+                return outcome;
+            }
+        };
+
+        info!(
+            "Running smoke test {}: {} {}",
+            self.name, outcome.method, outcome.url
+        );
+        let started = Instant::now();
+        let response = match request.timeout(REQUEST_TIMEOUT).send().await {
+            Ok(response) => response,
+            Err(err) => {
+                debug!("Smoke test {} failed: {err}", self.name);
+                outcome.error = Some(err.to_string());
+                return outcome;
+            }
+        };
+        outcome.latency = Some(started.elapsed());
+
+        let status = response.status().as_u16();
+        outcome.status_code = Some(status);
+        outcome.assertions.push(match self.status {
+            Some(expected) => AssertionOutcome {
+                description: format!("status is {expected}"),
+                passed: status == expected,
+                actual: Some(status.to_string()),
+            },
+            None => AssertionOutcome {
+                description: "status is 2xx".to_owned(),
+                passed: (200..300).contains(&status),
+                actual: Some(status.to_string()),
+            },
+        });
+
+        for (name, matcher) in &self.expect_headers {
+            let actual = response
+                .headers()
+                .get(name)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned());
+            outcome.assertions.push(AssertionOutcome {
+                description: format!("header {name} {matcher}"),
+                passed: actual
+                    .as_deref()
+                    .is_some_and(|actual| matcher.matches(actual)),
+                actual,
+            });
+        }
+
+        let body = match response.text().await {
+            Ok(body) => body,
+            Err(err) => {
+                outcome.error = Some(format!("unable to read the body: {err}"));
+                return outcome;
+            }
+        };
+
+        for matcher in &self.expect_body {
+            outcome.assertions.push(AssertionOutcome {
+                description: format!("body {matcher}"),
+                passed: matcher.matches(&body),
+                actual: None,
+            });
+        }
+
+        if !self.expect_json.is_empty() {
+            let json = serde_json::from_str::<Value>(&body);
+            for assertion in &self.expect_json {
+                outcome.assertions.push(match &json {
+                    Ok(json) => assertion.check(json),
+                    Err(err) => AssertionOutcome {
+                        description: assertion.to_string(),
+                        passed: false,
+                        actual: Some(format!("body isn't JSON: {err}")),
+                    },
+                });
+            }
+        }
+
+        outcome
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        services: &HashMap<String, SmokeService>,
+    ) -> std::result::Result<(String, reqwest::RequestBuilder), String> {
+        let own = self.service.as_deref();
+        let url = match (&self.url, own) {
+            (Some(url), _) => interpolate(url, own, services)?,
+            (None, Some(service)) => {
+                let static_url = services
+                    .get(service)
+                    .and_then(|s| s.static_url.as_deref())
+                    .ok_or_else(|| format!("service {service} has no public domain"))?;
+                let path = interpolate(&self.path, own, services)?;
+                format!("https://{static_url}/{}", path.trim_start_matches('/'))
+            }
+            (None, None) => return Err("neither url nor service is set".to_owned()),
+        };
+
+        let method = reqwest::Method::from_bytes(self.method.to_uppercase().as_bytes())
+            .map_err(|_| format!("invalid method {}", self.method))?;
+        let mut request = client.request(method, &url);
+        for (name, value) in &self.headers {
+            request = request.header(name, interpolate(value, own, services)?);
+        }
+        if let Some(body) = &self.body {
+            request = request.body(interpolate(body, own, services)?);
+        }
+
+        Ok((url, request))
+    }
+}
+
+impl JsonAssertion {
+    fn check(&self, json: &Value) -> AssertionOutcome {
+        let selected = match select(json, &self.path) {
+            Ok(selected) => selected,
+            Err(err) => {
+                return AssertionOutcome {
+                    description: self.to_string(),
+                    passed: false,
+                    actual: Some(err),
+                }
+            }
+        };
+
+        let passed = match &self.expect {
+            JsonExpectation::Equals(expected) => selected == Some(expected),
+            JsonExpectation::Exists(exists) => selected.is_some() == *exists,
+            JsonExpectation::Contains(expected) => selected
+                .and_then(Value::as_str)
+                .is_some_and(|actual| actual.contains(expected.as_str())),
+        };
+        AssertionOutcome {
+            description: self.to_string(),
+            passed,
+            actual: Some(selected.map_or_else(|| "missing".to_owned(), Value::to_string)),
+        }
+    }
+}
+
+impl fmt::Display for JsonAssertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.expect {
+            JsonExpectation::Equals(expected) => write!(f, "{} equals {expected}", self.path),
+            JsonExpectation::Exists(true) => write!(f, "{} exists", self.path),
+            JsonExpectation::Exists(false) => write!(f, "{} doesn't exist", self.path),
+            JsonExpectation::Contains(expected) => {
+                write!(f, "{} contains {expected:?}", self.path)
+            }
+        }
+    }
+}
+
+/// Replaces `${{service.NAME}}` and `${{NAME}}` references, like Railway does in variables
+fn interpolate(
+    value: &str,
+    own: Option<&str>,
+    services: &HashMap<String, SmokeService>,
+) -> std::result::Result<String, String> {
+    let mut interpolated = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${{") {
+        interpolated.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| format!("unterminated reference in {value}"))?;
+        let reference = rest[start + 3..start + end].trim();
+
+        let (service, name) = match reference.split_once('.') {
+            Some((service, name)) => (service, name),
+            None => (
+                own.ok_or_else(|| format!("{reference} needs a service"))?,
+                reference,
+            ),
+        };
+        let resolved = services
+            .get(service)
+            .and_then(|s| s.variables.get(name))
+            .ok_or_else(|| format!("unknown variable {service}.{name}"))?;
+        interpolated.push_str(resolved);
+
+        rest = &rest[start + end + 2..];
+    }
+    interpolated.push_str(rest);
+    Ok(interpolated)
+}
+
+/// Subset of JSONPath covering `$.field`, `$['field']` and `$[0]` chains
+fn select<'a>(value: &'a Value, path: &str) -> std::result::Result<Option<&'a Value>, String> {
+    let invalid = || format!("invalid JSONPath {path}");
+    let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+
+    let mut current = value;
+    while !rest.is_empty() {
+        let next = if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            rest = &after[end..];
+            current.get(&after[..end])
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let segment = &after[..end];
+            rest = &after[end + 1..];
+
+            let quoted = segment
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| segment.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+            match quoted {
+                Some(key) => current.get(key),
+                None => current.get(segment.parse::<usize>().map_err(|_| invalid())?),
+            }
+        } else {
+            return Err(invalid());
+        };
+
+        match next {
+            Some(next) => current = next,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn services() -> HashMap<String, SmokeService> {
+        HashMap::from([
+            (
+                "api".to_owned(),
+                SmokeService {
+                    static_url: None,
+                    variables: HashMap::from([("PORT".to_owned(), "8080".to_owned())]),
+                },
+            ),
+            (
+                "db".to_owned(),
+                SmokeService {
+                    static_url: None,
+                    variables: HashMap::from([("PGUSER".to_owned(), "postgres".to_owned())]),
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn selects_nested_keys_and_indices() {
+        let value = json!({ "data": { "items": [{ "id": 1 }, { "id": 2 }] } });
+        assert_eq!(select(&value, "$").unwrap(), Some(&value));
+        assert_eq!(
+            select(&value, "$.data.items[1].id").unwrap(),
+            Some(&json!(2))
+        );
+        assert_eq!(
+            select(&value, "$.data.items[0]").unwrap(),
+            Some(&json!({ "id": 1 }))
+        );
+    }
+
+    #[test]
+    fn selects_quoted_keys() {
+        let value = json!({ "content-type": { "a.b": [true] } });
+        assert_eq!(
+            select(&value, "$['content-type'][\"a.b\"][0]").unwrap(),
+            Some(&json!(true))
+        );
+    }
+
+    #[test]
+    fn missing_paths_select_nothing() {
+        let value = json!({ "items": [1] });
+        assert_eq!(select(&value, "$.missing.deeper").unwrap(), None);
+        assert_eq!(select(&value, "$.items[3]").unwrap(), None);
+        assert_eq!(select(&value, "$['items']['id']").unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_paths_fail() {
+        let value = json!({ "items": [1] });
+        for path in ["items", "$..items", "$.items[0", "$.items[first]", "$items"] {
+            assert_eq!(
+                select(&value, path),
+                Err(format!("invalid JSONPath {path}")),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn interpolates_references() {
+        let services = services();
+        assert_eq!(
+            interpolate("http://${{ PORT }}/${{db.PGUSER}}", Some("api"), &services).unwrap(),
+            "http://8080/postgres"
+        );
+        assert_eq!(
+            interpolate("no references", None, &services).unwrap(),
+            "no references"
+        );
+    }
+
+    #[test]
+    fn unknown_references_fail() {
+        let services = services();
+        assert_eq!(
+            interpolate("${{db.PASSWORD}}", Some("api"), &services),
+            Err("unknown variable db.PASSWORD".to_owned())
+        );
+        assert_eq!(
+            interpolate("${{cache.PORT}}", Some("api"), &services),
+            Err("unknown variable cache.PORT".to_owned())
+        );
+        assert_eq!(
+            interpolate("${{PORT}}", None, &services),
+            Err("PORT needs a service".to_owned())
+        );
+    }
+
+    #[test]
+    fn unterminated_references_fail() {
+        assert_eq!(
+            interpolate("http://${{api.PORT", Some("api"), &services()),
+            Err("unterminated reference in http://${{api.PORT".to_owned())
+        );
+    }
+}
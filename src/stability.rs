@@ -0,0 +1,183 @@
+use crate::{
+    outcome::{CrashOutcome, StabilityOutcome},
+    Deployment, DeploymentLog, DeploymentStatus, Result,
+};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use tracing::warn;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Deploy logs kept before each crash, the cause usually shows up right before it
+const LOGS_BEFORE_CRASH: chrono::Duration = chrono::Duration::seconds(30);
+const LOGS_AFTER_CRASH: chrono::Duration = chrono::Duration::seconds(5);
+
+pub struct Stability;
+
+impl Stability {
+    /// Watches a deployment until `window` has passed since it became active,
+    /// recording every time it stopped being up
+    pub async fn watch(
+        token: &str,
+        deployment_id: &str,
+        window: Duration,
+    ) -> Result<StabilityOutcome> {
+        let timeline = Deployment::timeline(token, deployment_id).await?;
+        let active_at = timeline.active_at();
+        let until =
+            active_at.unwrap_or_else(Utc::now) + chrono::Duration::seconds(window.as_secs() as i64);
+
+        let mut crashes = Vec::new();
+        let mut restarts = 0;
+        let remaining = (until - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+        let _ = tokio::time::timeout(
+            remaining,
+            Self::follow(token, deployment_id, &mut crashes, &mut restarts),
+        )
+        .await;
+
+        // A crash may have happened before watching started, or between polls
+        let timeline = Deployment::timeline(token, deployment_id).await?;
+        if let Some(crashed_at) = timeline.crashed_at() {
+            if crashed_at <= until && crashes.is_empty() {
+                crashes.push((crashed_at, timeline.status().clone()));
+            }
+        }
+
+        let logs = if crashes.is_empty() {
+            Vec::new()
+        } else {
+            Deployment::logs(token, deployment_id).await?
+        };
+
+        Ok(StabilityOutcome {
+            window,
+            active_at,
+            restarts,
+            crashes: crashes
+                .into_iter()
+                .map(|(at, status)| CrashOutcome {
+                    at,
+                    status,
+                    logs: DeploymentLog::between(
+                        &logs,
+                        at - LOGS_BEFORE_CRASH,
+                        at + LOGS_AFTER_CRASH,
+                    )
+                    .cloned()
+                    .collect(),
+                })
+                .collect(),
+        })
+    }
+
+    /// Records each transition out of an up status until cancelled, and counts a restart each
+    /// time the restart policy brings the deployment back up
+    async fn follow(
+        token: &str,
+        deployment_id: &str,
+        crashes: &mut Vec<(DateTime<Utc>, DeploymentStatus)>,
+        restarts: &mut usize,
+    ) {
+        let mut up = true;
+        let mut observe = |status: DeploymentStatus| {
+            let was_up = std::mem::replace(&mut up, status.is_success());
+            if was_up && !up {
+                crashes.push((Utc::now(), status));
+            } else if !was_up && up {
+                *restarts += 1;
+            }
+        };
+
+        match Deployment::subscribe_status(token, deployment_id).await {
+            Ok(mut subscription) => {
+                while let Some(event) = subscription.next().await {
+                    match event {
+                        Ok(event) => observe(event.status().clone()),
+                        Err(err) => {
+                            warn!("Status subscription of {deployment_id} failed, polling instead: {err}");
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(err) => {
+                warn!("Unable to subscribe to status of {deployment_id}, polling instead: {err}")
+            }
+        }
+
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            match Deployment::timeline(token, deployment_id).await {
+                Ok(timeline) => observe(timeline.status().clone()),
+                Err(err) => warn!("Unable to poll status of {deployment_id}: {err}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        railway::{
+            fake::{self, accept_subscription, send},
+            Endpoints,
+        },
+        Railway,
+    };
+
+    #[tokio::test]
+    async fn counts_restarts_after_crashes() {
+        let active_at = Utc::now();
+        let http = fake::serve_graphql(move |operation| match operation {
+            "deploymentEvents" => serde_json::json!({
+                "deployment": {
+                    "createdAt": active_at,
+                    "status": "SUCCESS",
+                    "statusUpdatedAt": active_at,
+                },
+                "deploymentEvents": { "edges": [] },
+            }),
+            "deploymentLogs" => serde_json::json!({
+                "deploymentLogs": [{
+                    "message": "thread 'main' panicked",
+                    "severity": "error",
+                    "timestamp": Utc::now(),
+                }],
+            }),
+            other => panic!("unexpected {other} query"),
+        })
+        .await;
+        // The restart policy bringing the crashed deployment back up
+        let ws = fake::serve_ws(|mut socket| async move {
+            let subscribe = accept_subscription(&mut socket).await;
+            for status in ["SUCCESS", "CRASHED", "SUCCESS"] {
+                send(
+                    &mut socket,
+                    serde_json::json!({
+                        "id": subscribe["id"],
+                        "type": "next",
+                        "payload": { "data": { "deployment": { "id": "d1", "status": status } } },
+                    }),
+                )
+                .await;
+            }
+            std::future::pending::<()>().await;
+        })
+        .await;
+
+        let stability = Railway::scoped(
+            Endpoints { http, ws },
+            Stability::watch("token", "d1", Duration::from_secs(1)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(stability.active_at, Some(active_at));
+        assert_eq!(stability.restarts, 1);
+        assert_eq!(stability.crashes.len(), 1);
+        assert_eq!(stability.crashes[0].status, DeploymentStatus::Crashed);
+        assert_eq!(stability.crashes[0].logs.len(), 1);
+        assert!(!stability.is_stable());
+    }
+}
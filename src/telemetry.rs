@@ -0,0 +1,52 @@
+use crate::Result;
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{
+    runtime,
+    trace::{Tracer, TracerProvider},
+    Resource,
+};
+use tracing::{error, Subscriber};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Standard OTLP variable, the collector spans are exported to over gRPC
+const ENDPOINT_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Exports spans to an OTLP collector, every template run being its own trace. Spans still
+/// batched are flushed when dropped
+pub struct Telemetry {
+    provider: TracerProvider,
+}
+
+impl Telemetry {
+    /// Only exports when `OTEL_EXPORTER_OTLP_ENDPOINT` is set, e.g. to `http://localhost:4317`
+    pub fn from_env() -> Result<Option<Self>> {
+        if std::env::var_os(ENDPOINT_VAR).is_none() {
+            return Ok(None);
+        }
+
+        let exporter = SpanExporter::builder().with_tonic().build()?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new([KeyValue::new("service.name", "crater")]))
+            .build();
+        Ok(Some(Self { provider }))
+    }
+
+    /// Turns `tracing` spans into OpenTelemetry ones
+    pub fn layer<S>(&self) -> OpenTelemetryLayer<S, Tracer>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.provider.tracer("crater"))
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(err) = self.provider.shutdown() {
+            error!("Unable to flush traces: {err}");
+        }
+    }
+}
@@ -0,0 +1,151 @@
+mod logs;
+mod state;
+mod view;
+
+pub use logs::TuiLogs;
+
+use crate::{pipeline::Pipeline, Config, Result, RunSummary};
+use crossterm::{
+    event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures_util::StreamExt;
+use ratatui::{backend::CrosstermBackend, Terminal};
+use state::{Dashboard, Focus};
+use std::{io, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Runs every template like [`crate::run`] while showing their progress live, `logs` being
+/// where the tracing subscriber writes to
+pub async fn tui(token: String, config: Config, logs: TuiLogs) -> Result<RunSummary> {
+    let pipeline = Pipeline::default();
+    let mut events = pipeline.subscribe();
+    let controls = pipeline.controls();
+    let mut run = tokio::spawn(crate::run_with(token, config, pipeline));
+
+    let mut terminal = Screen::enter()?;
+    let mut keys = EventStream::new();
+    let mut redraw = tokio::time::interval(REDRAW_INTERVAL);
+    let mut dashboard = Dashboard::new();
+    let mut result = None;
+    let mut subscribed = true;
+
+    loop {
+        tokio::select! {
+            event = events.recv(), if subscribed => match event {
+                Ok(event) => dashboard.apply(event),
+                // The dashboard catches up with the next events
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => subscribed = false,
+            },
+            joined = &mut run, if result.is_none() => {
+                match joined {
+                    Ok(run_result) => result = Some(run_result),
+                    Err(err) => std::panic::resume_unwind(err.into_panic()),
+                }
+                while let Ok(event) = events.try_recv() {
+                    dashboard.apply(event);
+                }
+                dashboard.done = true;
+                // Nothing left to look at once stopped from the dashboard
+                if dashboard.stopping {
+                    break;
+                }
+            }
+            Some(key) = keys.next() => {
+                let Event::Key(key) = key? else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                if is_quit(&key) {
+                    if result.is_some() {
+                        break;
+                    }
+                    dashboard.stopping = true;
+                    controls.stop();
+                    continue;
+                }
+
+                match key.code {
+                    KeyCode::Esc => dashboard.detail = None,
+                    KeyCode::Tab => dashboard.toggle_focus(),
+                    KeyCode::Down => dashboard.select_next(),
+                    KeyCode::Up => dashboard.select_previous(),
+                    KeyCode::Enter if dashboard.focus == Focus::Finished => {
+                        dashboard.detail = (!dashboard.finished.is_empty())
+                            .then_some(dashboard.selected_finished);
+                    }
+                    KeyCode::Char('a') => {
+                        if let Some(run) = dashboard.selected_run() {
+                            controls.abort(run);
+                        }
+                    }
+                    KeyCode::Char('k') => {
+                        if let Some(run) = dashboard.selected_run() {
+                            controls.keep(run);
+                            dashboard.kept.insert(run);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ = redraw.tick() => {}
+        }
+
+        let tail = logs.tail(50);
+        terminal.draw(|frame| view::render(frame, &dashboard, &tail))?;
+    }
+
+    drop(terminal);
+    match result {
+        Some(result) => result,
+        None => run
+            .await
+            .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic())),
+    }
+}
+
+fn is_quit(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('q')
+        || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+}
+
+/// Alternate screen in raw mode, the terminal is restored when dropped, panics included
+struct Screen(Terminal<CrosstermBackend<io::Stdout>>);
+
+impl Screen {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        terminal.hide_cursor()?;
+        Ok(Self(terminal))
+    }
+}
+
+impl std::ops::Deref for Screen {
+    type Target = Terminal<CrosstermBackend<io::Stdout>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for Screen {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        let _ = self.0.show_cursor();
+    }
+}
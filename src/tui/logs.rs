@@ -0,0 +1,72 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, Mutex, PoisonError},
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Lines kept for the log pane, older ones are dropped
+const MAX_LINES: usize = 500;
+
+/// Where the dashboard's log lines come from, given to the tracing subscriber in place of
+/// stdout while the dashboard owns the terminal
+#[derive(Clone, Default)]
+pub struct TuiLogs {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl TuiLogs {
+    /// The last `count` lines, oldest first
+    pub fn tail(&self, count: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap_or_else(PoisonError::into_inner);
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+
+    fn push(&self, text: &str) {
+        let mut lines = self.lines.lock().unwrap_or_else(PoisonError::into_inner);
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            if lines.len() == MAX_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_owned());
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for TuiLogs {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogWriter {
+            logs: self.clone(),
+            buffer: Vec::new(),
+        }
+    }
+}
+
+/// Buffers a single event, which is only stored once complete
+pub struct LogWriter {
+    logs: TuiLogs,
+    buffer: Vec<u8>,
+}
+
+impl io::Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        self.logs.push(&String::from_utf8_lossy(&self.buffer));
+    }
+}
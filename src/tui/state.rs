@@ -0,0 +1,210 @@
+use crate::{
+    outcome::{StageKind, TemplateOutcome},
+    RunEvent, RunSummary, StageStatus,
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::Instant,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum Focus {
+    Workers,
+    Finished,
+}
+
+/// Template a worker is running
+pub(super) struct Current {
+    pub template: String,
+    /// What [`crate::pipeline::Controls`] know the run by
+    pub run: u64,
+    pub stage: Option<StageKind>,
+    pub started: Instant,
+    pub stage_started: Instant,
+}
+
+#[derive(Default)]
+pub(super) struct Worker {
+    pub queued: usize,
+    pub done: usize,
+    pub current: Option<Current>,
+}
+
+/// Project deployed and not deleted yet
+pub(super) struct LiveProject {
+    pub template: String,
+    /// Run that deployed it, unknown if its worker moved on before the event arrived
+    pub run: Option<u64>,
+    /// Set when deleting it failed, it has to be deleted by hand
+    pub cleanup_error: Option<String>,
+}
+
+/// Everything the dashboard shows, built from the run's events
+pub(super) struct Dashboard {
+    pub started: Instant,
+    pub workers: Vec<Worker>,
+    /// By project id
+    pub live: BTreeMap<String, LiveProject>,
+    pub finished: Vec<Arc<TemplateOutcome>>,
+    pub counts: HashMap<StageStatus, usize>,
+    /// Runs whose project is kept alive instead of deleted
+    pub kept: HashSet<u64>,
+    pub summary: Option<RunSummary>,
+    /// Set once the run returned, successfully or not
+    pub done: bool,
+    /// Set once quitting was asked for, the run stops after cleaning up
+    pub stopping: bool,
+    pub focus: Focus,
+    pub selected_worker: usize,
+    pub selected_finished: usize,
+    /// Index in `finished` of the template shown in full
+    pub detail: Option<usize>,
+    workers_by_template: HashMap<String, usize>,
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            workers: Vec::new(),
+            live: BTreeMap::new(),
+            finished: Vec::new(),
+            counts: HashMap::new(),
+            kept: HashSet::new(),
+            summary: None,
+            done: false,
+            stopping: false,
+            focus: Focus::Workers,
+            selected_worker: 0,
+            selected_finished: 0,
+            detail: None,
+            workers_by_template: HashMap::new(),
+        }
+    }
+
+    pub fn apply(&mut self, event: RunEvent) {
+        match event {
+            RunEvent::TemplateQueued { template, worker } => {
+                if self.workers.len() <= worker {
+                    self.workers.resize_with(worker + 1, Worker::default);
+                }
+                self.workers[worker].queued += 1;
+                self.workers_by_template.insert(template, worker);
+            }
+            RunEvent::StageStarted {
+                template,
+                run,
+                stage,
+            } => {
+                let Some(worker) = self.worker_mut(&template) else {
+                    return;
+                };
+                let now = Instant::now();
+                match &mut worker.current {
+                    Some(current) if current.run == run => {
+                        current.stage = Some(stage);
+                        current.stage_started = now;
+                    }
+                    current => {
+                        *current = Some(Current {
+                            template,
+                            run,
+                            stage: Some(stage),
+                            started: now,
+                            stage_started: now,
+                        });
+                    }
+                }
+            }
+            RunEvent::Deployed {
+                template,
+                project_id,
+            } => {
+                let run = self
+                    .worker_mut(&template)
+                    .and_then(|worker| worker.current.as_ref())
+                    .filter(|current| current.template == template)
+                    .map(|current| current.run);
+                self.live.insert(
+                    project_id,
+                    LiveProject {
+                        template,
+                        run,
+                        cleanup_error: None,
+                    },
+                );
+            }
+            RunEvent::Cleaned {
+                project_id, error, ..
+            } => match error {
+                Some(error) => {
+                    if let Some(project) = self.live.get_mut(&project_id) {
+                        project.cleanup_error = Some(error);
+                    }
+                }
+                None => {
+                    self.live.remove(&project_id);
+                }
+            },
+            RunEvent::TemplateFinished { outcome } => {
+                if let Some(worker) = self.worker_mut(outcome.code()) {
+                    worker.current = None;
+                    worker.done += 1;
+                }
+                *self.counts.entry(outcome.status()).or_default() += 1;
+                self.finished.push(outcome);
+            }
+            RunEvent::RunFinished { summary } => self.summary = Some(summary),
+            RunEvent::Deploying { .. }
+            | RunEvent::WorkflowComplete { .. }
+            | RunEvent::BuildStatusChanged { .. }
+            | RunEvent::HealthcheckResult { .. } => {}
+        }
+    }
+
+    pub fn count(&self, status: StageStatus) -> usize {
+        self.counts.get(&status).copied().unwrap_or_default()
+    }
+
+    /// Run going on the selected worker
+    pub fn selected_run(&self) -> Option<u64> {
+        self.workers
+            .get(self.selected_worker)?
+            .current
+            .as_ref()
+            .map(|c| c.run)
+    }
+
+    pub fn select_next(&mut self) {
+        match self.focus {
+            Focus::Workers => {
+                self.selected_worker =
+                    (self.selected_worker + 1).min(self.workers.len().saturating_sub(1));
+            }
+            Focus::Finished => {
+                self.selected_finished =
+                    (self.selected_finished + 1).min(self.finished.len().saturating_sub(1));
+            }
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        match self.focus {
+            Focus::Workers => self.selected_worker = self.selected_worker.saturating_sub(1),
+            Focus::Finished => self.selected_finished = self.selected_finished.saturating_sub(1),
+        }
+    }
+
+    pub fn toggle_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::Workers => Focus::Finished,
+            Focus::Finished => Focus::Workers,
+        };
+    }
+
+    fn worker_mut(&mut self, template: &str) -> Option<&mut Worker> {
+        let worker = *self.workers_by_template.get(template)?;
+        self.workers.get_mut(worker)
+    }
+}
@@ -0,0 +1,264 @@
+use super::state::{Dashboard, Focus};
+use crate::{outcome::TemplateOutcome, report::format_duration, StageStatus};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState},
+    Frame,
+};
+
+const HELP: &str =
+    "tab switch pane · ↑↓ select · a abort template · k keep project · enter details · q quit";
+
+pub(super) fn render(frame: &mut Frame, dashboard: &Dashboard, logs: &[String]) {
+    let [header, workers, middle, log_pane, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(dashboard.workers.len() as u16 + 3),
+        Constraint::Min(6),
+        Constraint::Length(12),
+        Constraint::Length(1),
+    ])
+    .areas(frame.size());
+    let [live, finished] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(middle);
+
+    render_header(frame, dashboard, header);
+    render_workers(frame, dashboard, workers);
+    render_live(frame, dashboard, live);
+    render_finished(frame, dashboard, finished);
+
+    let lines: Vec<_> = logs
+        .iter()
+        .skip(
+            logs.len()
+                .saturating_sub(log_pane.height.saturating_sub(2).into()),
+        )
+        .map(|l| Line::raw(l.as_str()))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Logs ")),
+        log_pane,
+    );
+    frame.render_widget(Paragraph::new(HELP).dark_gray(), footer);
+
+    if let Some(outcome) = dashboard.detail.and_then(|i| dashboard.finished.get(i)) {
+        render_detail(frame, outcome);
+    }
+}
+
+fn render_header(frame: &mut Frame, dashboard: &Dashboard, area: Rect) {
+    let state = match (dashboard.done, dashboard.stopping) {
+        (true, _) => "finished, q to exit".bold(),
+        (false, true) => "stopping, cleaning up".yellow().bold(),
+        (false, false) => "running".bold(),
+    };
+    let line = Line::from(vec![
+        " crater ".reversed(),
+        Span::raw(" "),
+        state,
+        Span::raw(format!(
+            "  {}  ",
+            format_duration(dashboard.started.elapsed())
+        )),
+        status_span(StageStatus::Passed, dashboard.count(StageStatus::Passed)),
+        status_span(StageStatus::Flaky, dashboard.count(StageStatus::Flaky)),
+        status_span(StageStatus::Failed, dashboard.count(StageStatus::Failed)),
+        status_span(
+            StageStatus::TimedOut,
+            dashboard.count(StageStatus::TimedOut),
+        ),
+        status_span(StageStatus::Skipped, dashboard.count(StageStatus::Skipped)),
+        Span::raw(format!("live projects {}", dashboard.live.len())),
+    ]);
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+fn render_workers(frame: &mut Frame, dashboard: &Dashboard, area: Rect) {
+    let rows = dashboard.workers.iter().enumerate().map(|(i, worker)| {
+        let progress = format!("{}/{}", worker.done, worker.queued);
+        match &worker.current {
+            Some(current) => {
+                let kept = if dashboard.kept.contains(&current.run) {
+                    " (kept)"
+                } else {
+                    ""
+                };
+                Row::new(vec![
+                    Cell::from(format!("#{}", i + 1)),
+                    Cell::from(format!("{}{kept}", current.template)),
+                    Cell::from(
+                        current
+                            .stage
+                            .as_ref()
+                            .map(ToString::to_string)
+                            .unwrap_or_default(),
+                    ),
+                    Cell::from(format_duration(current.stage_started.elapsed())),
+                    Cell::from(format_duration(current.started.elapsed())),
+                    Cell::from(progress),
+                ])
+            }
+            None => Row::new(vec![
+                Cell::from(format!("#{}", i + 1)),
+                Cell::from("idle".dark_gray()),
+                Cell::from(""),
+                Cell::from(""),
+                Cell::from(""),
+                Cell::from(progress),
+            ]),
+        }
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(4),
+            Constraint::Fill(2),
+            Constraint::Fill(1),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(8),
+        ],
+    )
+    .header(
+        Row::new(["", "Template", "Stage", "Stage", "Total", "Done"])
+            .style(Style::new().add_modifier(Modifier::BOLD)),
+    )
+    .block(pane(" Workers ", dashboard.focus == Focus::Workers))
+    .highlight_style(Style::new().reversed());
+
+    let mut state = TableState::default().with_selected(Some(dashboard.selected_worker));
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+fn render_live(frame: &mut Frame, dashboard: &Dashboard, area: Rect) {
+    let items = dashboard.live.iter().map(|(project_id, project)| {
+        let mut line = vec![
+            Span::raw(project.template.clone()),
+            Span::raw(" "),
+            Span::raw(project_id.clone()).dark_gray(),
+        ];
+        if project.cleanup_error.is_some() {
+            line.push(" cleanup failed".red());
+        } else if project.run.is_some_and(|run| dashboard.kept.contains(&run)) {
+            line.push(" kept".yellow());
+        }
+        ListItem::new(Line::from(line))
+    });
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title(" Live projects ")),
+        area,
+    );
+}
+
+fn render_finished(frame: &mut Frame, dashboard: &Dashboard, area: Rect) {
+    let items = dashboard.finished.iter().map(|outcome| {
+        let mut line = vec![
+            status_label(outcome.status()),
+            Span::raw(format!(
+                " {} {}",
+                outcome.code(),
+                format_duration(outcome.duration())
+            )),
+        ];
+        if let Some(failure) = outcome.failure() {
+            line.push(Span::raw(format!(" {}", failure.stage())).dark_gray());
+        }
+        ListItem::new(Line::from(line))
+    });
+    let list = List::new(items)
+        .block(pane(" Finished ", dashboard.focus == Focus::Finished))
+        .highlight_style(Style::new().reversed());
+
+    let mut state = ListState::default()
+        .with_selected((!dashboard.finished.is_empty()).then_some(dashboard.selected_finished));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_detail(frame: &mut Frame, outcome: &TemplateOutcome) {
+    let area = centered(frame.size(), 80, 80);
+    let mut lines = vec![
+        Line::from(vec![
+            Span::raw(outcome.code().clone()).bold(),
+            Span::raw(" "),
+            status_label(outcome.status()),
+            Span::raw(format!(" in {}", format_duration(outcome.duration()))),
+        ]),
+        Line::raw(format!(
+            "project {}",
+            outcome.project_id().as_deref().unwrap_or("-")
+        )),
+        Line::raw(""),
+        Line::raw("Stages").bold(),
+    ];
+    for stage in outcome.stages() {
+        let mut line = vec![
+            Span::raw(format!("  {:<12} ", stage.stage().to_string())),
+            status_label(stage.status()),
+            Span::raw(format!(" {}", format_duration(stage.duration()))),
+        ];
+        if let Some(error) = stage.error() {
+            line.push(Span::raw(format!(" {error}")).red());
+        }
+        lines.push(Line::from(line));
+    }
+
+    lines.push(Line::raw(""));
+    lines.push(Line::raw("Services").bold());
+    for service in outcome.services() {
+        let status = service
+            .status
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "-".to_owned());
+        let url = service.static_url.as_deref().unwrap_or_default();
+        lines.push(Line::raw(format!("  {:<20} {status} {url}", service.name)));
+    }
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Details, esc to close ")),
+        area,
+    );
+}
+
+fn pane(title: &str, focused: bool) -> Block<'_> {
+    let block = Block::bordered().title(title);
+    if focused {
+        block.border_style(Style::new().fg(Color::Cyan))
+    } else {
+        block
+    }
+}
+
+fn status_label(status: StageStatus) -> Span<'static> {
+    let label = Span::raw(status.to_string());
+    match status {
+        StageStatus::Passed => label.green(),
+        StageStatus::Flaky => label.yellow(),
+        StageStatus::Failed | StageStatus::TimedOut => label.red(),
+        StageStatus::Skipped => label.dark_gray(),
+    }
+}
+
+fn status_span(status: StageStatus, count: usize) -> Span<'static> {
+    let label = status_label(status);
+    Span::styled(format!("{status} {count}  "), label.style)
+}
+
+/// `width` and `height` percent of `area`, in its middle
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let [_, area, _] = Layout::vertical([
+        Constraint::Percentage((100 - height) / 2),
+        Constraint::Percentage(height),
+        Constraint::Percentage((100 - height) / 2),
+    ])
+    .areas(area);
+    let [_, area, _] = Layout::horizontal([
+        Constraint::Percentage((100 - width) / 2),
+        Constraint::Percentage(width),
+        Constraint::Percentage((100 - width) / 2),
+    ])
+    .areas(area);
+    area
+}